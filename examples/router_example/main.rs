@@ -5,6 +5,7 @@ use std::time::UNIX_EPOCH;
 
 use serde::Deserialize;
 use serde::Serialize;
+use yahf::body::Body;
 use yahf::handler::Json;
 use yahf::request::Request;
 use yahf::result::Result;
@@ -18,7 +19,7 @@ struct ComputationBody {
     value: u32,
 }
 
-async fn log_middleware(req: Result<Request<String>>) -> Result<Request<String>> {
+async fn log_middleware(req: Result<Request<Body>>) -> Result<Request<Body>> {
     match req.into_inner() {
         Ok(req) => {
             println!(
@@ -37,7 +38,7 @@ async fn log_middleware(req: Result<Request<String>>) -> Result<Request<String>>
     }
 }
 
-async fn log_error(res: Result<Response<String>>) -> Result<Response<String>> {
+async fn log_error(res: Result<Response<Body>>) -> Result<Response<Body>> {
     match res.into_inner() {
         Err(err) => {
             println!(
@@ -0,0 +1,174 @@
+//! Parsing of the `Accept` header
+
+/// Find which of the `offers` the `accept` header prefers, by its quality value
+///
+/// A missing header accepts anything, so the first offer is returned. When more than one offer
+/// has the same quality, the first one wins. [None] means that nothing is acceptable
+pub(crate) fn preferred(accept: Option<&str>, offers: &[&str]) -> Option<usize> {
+    let accept = match accept {
+        Some(accept) if !accept.trim().is_empty() => accept,
+        _ => return (!offers.is_empty()).then_some(0),
+    };
+
+    let ranges: Vec<_> = accept
+        .split(',')
+        .filter_map(MediaRange::parse)
+        .collect();
+
+    offers
+        .iter()
+        .enumerate()
+        .filter_map(|(index, offer)| {
+            ranges
+                .iter()
+                .filter(|range| range.matches(offer))
+                .max_by_key(|range| range.specificity())
+                .map(|range| (index, range.quality))
+        })
+        .filter(|(_, quality)| *quality > 0)
+        .fold(
+            None,
+            |best: Option<(usize, u16)>, (index, quality)| match best {
+                Some((_, best_quality)) if best_quality >= quality => best,
+                _ => Some((index, quality)),
+            },
+        )
+        .map(|(index, _)| index)
+}
+
+struct MediaRange<'a> {
+    kind: &'a str,
+    subtype: &'a str,
+    /// Quality value in thousandths
+    quality: u16,
+}
+
+impl<'a> MediaRange<'a> {
+    fn parse(value: &'a str) -> Option<Self> {
+        let mut params = value.split(';');
+        let (kind, subtype) = params
+            .next()?
+            .trim()
+            .split_once('/')?;
+
+        let quality = params
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim() == "q")
+            .and_then(|(_, value)| {
+                value
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+            })
+            .map_or(1000, |quality| (quality.clamp(0.0, 1.0) * 1000.0) as u16);
+
+        Some(Self {
+            kind: kind.trim(),
+            subtype: subtype.trim(),
+            quality,
+        })
+    }
+
+    fn matches(&self, media_type: &str) -> bool {
+        let (kind, subtype) = media_type
+            .split(';')
+            .next()
+            .and_then(|media_type| media_type.split_once('/'))
+            .unwrap_or((media_type, ""));
+
+        (self.kind == "*"
+            || self
+                .kind
+                .eq_ignore_ascii_case(kind))
+            && (self.subtype == "*"
+                || self
+                    .subtype
+                    .eq_ignore_ascii_case(subtype))
+    }
+
+    fn specificity(&self) -> u8 {
+        match (self.kind, self.subtype) {
+            ("*", _) => 0,
+            (_, "*") => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Add `Accept` to the `Vary` header, for a response chosen by the `Accept` header of the request
+pub(crate) fn vary(headers: &mut http::HeaderMap) {
+    let listed = headers
+        .get_all(http::header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|name| {
+            let name = name.trim();
+            name == "*" || name.eq_ignore_ascii_case("accept")
+        });
+
+    if !listed {
+        headers.append(http::header::VARY, http::HeaderValue::from_static("accept"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{preferred, vary};
+
+    const OFFERS: [&str; 3] = ["text/plain", "application/json", "application/problem+json"];
+
+    #[test]
+    fn test_missing_header_prefers_the_first_offer() {
+        assert_eq!(preferred(None, &OFFERS), Some(0));
+        assert_eq!(preferred(Some("*/*"), &OFFERS), Some(0));
+    }
+
+    #[test]
+    fn test_quality_values() {
+        assert_eq!(
+            preferred(Some("text/plain;q=0.5, application/problem+json"), &OFFERS),
+            Some(2)
+        );
+        assert_eq!(
+            preferred(Some("application/*;q=0.8, text/*;q=0.9"), &OFFERS),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_most_specific_range_wins() {
+        assert_eq!(
+            preferred(Some("*/*;q=0.1, application/json;q=0"), &OFFERS[1..2]),
+            None
+        );
+        assert_eq!(
+            preferred(Some("application/*, application/json;q=0"), &OFFERS),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_nothing_acceptable() {
+        assert_eq!(preferred(Some("image/png"), &OFFERS), None);
+    }
+
+    #[test]
+    fn test_vary() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            http::header::VARY,
+            http::HeaderValue::from_static("Accept-Encoding"),
+        );
+        vary(&mut headers);
+        vary(&mut headers);
+
+        assert_eq!(
+            headers
+                .get_all(http::header::VARY)
+                .iter()
+                .collect::<Vec<_>>(),
+            ["Accept-Encoding", "accept"]
+        );
+    }
+}
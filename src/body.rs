@@ -0,0 +1,250 @@
+//! Body of the [Requests](crate::request::Request) and [Responses](crate::response::Response)
+
+use std::{
+    fmt::Debug,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+
+use crate::{error::Error, result::InternalResult};
+
+/// Error produced by a streaming [Body]
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+type BoxStream = Pin<Box<dyn Stream<Item = Result<Bytes, BoxError>> + Send>>;
+
+/// Body of a [Request](crate::request::Request) or a [Response](crate::response::Response)
+///
+/// A [Body] is either a sequence of bytes already in memory, or a [Stream] of chunks that are
+/// produced as they are read. Nothing is buffered unless it's asked for, with [Body::bytes], so
+/// a handler can receive or send large and binary payloads:
+///
+/// ```rust
+/// use futures::StreamExt;
+/// use yahf::body::Body;
+///
+/// // Count the bytes of the upload, without holding it in memory
+/// async fn upload(mut body: Body) -> String {
+///     let mut size = 0;
+///     while let Some(Ok(chunk)) = body.next().await {
+///         size += chunk.len();
+///     }
+///
+///     size.to_string()
+/// }
+///
+/// // Stream the response, chunk by chunk
+/// async fn download() -> Body {
+///     Body::from_stream(futures::stream::iter(
+///         ["Hello", " ", "world"].map(Ok::<_, std::io::Error>),
+///     ))
+/// }
+/// # let router = yahf::router::Router::new()
+/// #     .post("/upload", upload, &(), &String::with_capacity(0))
+/// #     .get("/download", download, &(), &());
+/// ```
+///
+/// Streaming a request [Body] doesn't lift the body limit: it's still bound by the
+/// [Limits](crate::limits::Limits) of the [Server](crate::server::Server), 8 MiB by default, and
+/// answered with a `413 Payload Too Large` past it. Routes taking larger uploads set their own
+/// [BodyLimit](crate::limits::BodyLimit)
+pub struct Body(Inner);
+
+enum Inner {
+    Full(Bytes),
+    Stream(BoxStream),
+}
+
+impl Body {
+    /// Create an empty [Body]
+    pub fn empty() -> Self {
+        Self(Inner::Full(Bytes::new()))
+    }
+
+    /// Create a [Body] from a [Stream] of chunks
+    pub fn from_stream<S, B, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<B, E>> + Send + 'static,
+        B: Into<Bytes>,
+        E: Into<BoxError>,
+    {
+        Self(Inner::Stream(Box::pin(stream.map(|chunk| {
+            chunk
+                .map(Into::into)
+                .map_err(Into::into)
+        }))))
+    }
+
+    /// Return the bytes of the [Body] when they're already in memory, or [None] for a streaming
+    /// [Body]
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match &self.0 {
+            Inner::Full(bytes) => Some(bytes),
+            Inner::Stream(_) => None,
+        }
+    }
+
+    /// Read the whole [Body] into memory
+    ///
+    /// When the stream fails, the error is answered with a `400 Bad Request`, unless the stream
+    /// failed with an [Error], like going over the [body limit](crate::limits::Limits)
+    pub async fn bytes(self) -> InternalResult<Bytes> {
+        match self.0 {
+            Inner::Full(bytes) => Ok(bytes),
+            Inner::Stream(mut stream) => {
+                let mut buffer = BytesMut::new();
+                while let Some(chunk) = stream.next().await {
+                    let chunk = chunk.map_err(|err| match err.downcast::<Error>() {
+                        Ok(err) => *err,
+                        Err(err) => Error::new(format!("Failed to read the body: {}", err), 400),
+                    })?;
+                    buffer.extend_from_slice(&chunk);
+                }
+
+                Ok(buffer.freeze())
+            }
+        }
+    }
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Debug for Body {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Inner::Full(bytes) => f
+                .debug_tuple("Body")
+                .field(bytes)
+                .finish(),
+            Inner::Stream(_) => f
+                .debug_tuple("Body")
+                .field(&"Stream")
+                .finish(),
+        }
+    }
+}
+
+impl Stream for Body {
+    type Item = Result<Bytes, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match &mut self.0 {
+            Inner::Full(bytes) if bytes.is_empty() => Poll::Ready(None),
+            Inner::Full(bytes) => Poll::Ready(Some(Ok(std::mem::take(bytes)))),
+            Inner::Stream(stream) => stream.as_mut().poll_next(cx),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            Inner::Full(bytes) if bytes.is_empty() => (0, Some(0)),
+            Inner::Full(_) => (1, Some(1)),
+            Inner::Stream(stream) => stream.size_hint(),
+        }
+    }
+}
+
+impl From<Bytes> for Body {
+    fn from(value: Bytes) -> Self {
+        Self(Inner::Full(value))
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Self::from(Bytes::from(value))
+    }
+}
+
+impl From<String> for Body {
+    fn from(value: String) -> Self {
+        Self::from(Bytes::from(value))
+    }
+}
+
+impl From<&'static str> for Body {
+    fn from(value: &'static str) -> Self {
+        Self::from(Bytes::from_static(value.as_bytes()))
+    }
+}
+
+impl From<hyper::Body> for Body {
+    fn from(value: hyper::Body) -> Self {
+        // Keep knowing that a request without a body has none, since the stream can't tell
+        match hyper::body::HttpBody::is_end_stream(&value) {
+            true => Self::empty(),
+            false => Self::from_stream(value),
+        }
+    }
+}
+
+impl From<Body> for hyper::Body {
+    fn from(value: Body) -> Self {
+        match value.0 {
+            Inner::Full(bytes) => hyper::Body::from(bytes),
+            Inner::Stream(stream) => hyper::Body::wrap_stream(stream),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::StreamExt;
+
+    use super::Body;
+
+    #[tokio::test]
+    async fn test_full_body() {
+        let body = Body::from("Hello world");
+
+        assert_eq!(body.as_bytes(), Some(&Bytes::from("Hello world")));
+        assert_eq!(body.bytes().await.unwrap(), "Hello world");
+    }
+
+    #[tokio::test]
+    async fn test_stream_body_is_buffered_on_demand() {
+        let body = Body::from_stream(futures::stream::iter(
+            [&b"\x00\x9f"[..], &b"\xff"[..]].map(Ok::<_, std::io::Error>),
+        ));
+
+        assert!(body.as_bytes().is_none());
+        assert_eq!(body.bytes().await.unwrap(), &b"\x00\x9f\xff"[..]);
+    }
+
+    #[tokio::test]
+    async fn test_failing_stream_is_a_bad_request() {
+        let body = Body::from_stream(futures::stream::iter([
+            Ok(Bytes::from("chunk")),
+            Err(std::io::Error::other("connection reset")),
+        ]));
+
+        assert_eq!(
+            body.bytes()
+                .await
+                .unwrap_err()
+                .code(),
+            &400
+        );
+    }
+
+    #[tokio::test]
+    async fn test_full_body_as_stream() {
+        let chunks: Vec<_> = Body::from("chunk")
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 1);
+        assert!(Body::empty()
+            .next()
+            .await
+            .is_none());
+    }
+}
@@ -0,0 +1,478 @@
+//! Connections accepted by a [Server](crate::server::Server), and the timeouts of the requests
+//! read from them
+
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures::{task::AtomicWaker, Future, Stream};
+use hyper::{
+    body::{HttpBody, SizeHint},
+    server::accept::Accept,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{Instant, Sleep},
+};
+
+use crate::{
+    body::{Body, BoxError},
+    error::Error,
+};
+
+/// Connections that are still open
+#[derive(Debug, Default)]
+pub(crate) struct Connections {
+    open: AtomicUsize,
+    closed: AtomicWaker,
+}
+
+impl Connections {
+    pub(crate) fn open(&self) -> usize {
+        self.open
+            .load(Ordering::SeqCst)
+    }
+}
+
+/// Count a connection as open, until it's dropped
+struct ConnectionGuard(Arc<Connections>);
+
+impl ConnectionGuard {
+    fn open(connections: Arc<Connections>) -> Self {
+        connections
+            .open
+            .fetch_add(1, Ordering::SeqCst);
+        Self(connections)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0
+            .open
+            .fetch_sub(1, Ordering::SeqCst);
+        self.0.closed.wake();
+    }
+}
+
+/// [Accept] that stops accepting once there are `max_connections` open, and closes the HTTP/1
+/// connections that stay idle for longer than the `idle_timeout`
+///
+/// HTTP/2 connections are left to hyper, since closing their socket would drop them without a
+/// `GOAWAY`
+pub(crate) struct Incoming<I> {
+    inner: Pin<Box<I>>,
+    connections: Arc<Connections>,
+    max_connections: Option<usize>,
+    idle_timeout: Option<Duration>,
+}
+
+impl<I> Incoming<I> {
+    pub(crate) fn new(
+        inner: I,
+        connections: Arc<Connections>,
+        max_connections: Option<usize>,
+        idle_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            inner: Box::pin(inner),
+            connections,
+            max_connections,
+            idle_timeout,
+        }
+    }
+}
+
+impl<I> Accept for Incoming<I>
+where
+    I: Accept,
+{
+    type Conn = Connection<I::Conn>;
+    type Error = I::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        if let Some(max_connections) = self.max_connections {
+            // Registered before checking, so a connection closed in between still wakes us
+            self.connections
+                .closed
+                .register(cx.waker());
+            if self.connections.open() >= max_connections {
+                return Poll::Pending;
+            }
+        }
+
+        let this = &mut *self;
+        this.inner
+            .as_mut()
+            .poll_accept(cx)
+            .map(|conn| {
+                conn.map(|conn| {
+                    conn.map(|conn| Connection {
+                        inner: conn,
+                        idle: this
+                            .idle_timeout
+                            .map(Idle::new),
+                        sniffed: false,
+                        requests: Requests::default(),
+                        _guard: ConnectionGuard::open(this.connections.clone()),
+                    })
+                })
+            })
+    }
+}
+
+/// Requests of a [Connection] that weren't answered yet
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Requests(Arc<AtomicUsize>);
+
+impl Requests {
+    /// Count a request as in flight, until the returned guard is dropped
+    pub(crate) fn start(&self) -> InFlight {
+        self.0
+            .fetch_add(1, Ordering::SeqCst);
+        InFlight(self.0.clone())
+    }
+
+    fn is_idle(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == 0
+    }
+}
+
+/// Request counted as in flight by its [Requests]
+pub(crate) struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0
+            .fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Body of a response, that keeps its request [InFlight] until it's written whole or dropped
+///
+/// Otherwise a streamed body slower than the idle timeout would get its connection closed halfway
+pub(crate) struct InFlightBody {
+    inner: hyper::Body,
+    in_flight: Option<InFlight>,
+}
+
+impl InFlightBody {
+    pub(crate) fn new(inner: hyper::Body, in_flight: InFlight) -> Self {
+        Self {
+            inner,
+            in_flight: Some(in_flight),
+        }
+    }
+}
+
+impl HttpBody for InFlightBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let data = Pin::new(&mut self.inner).poll_data(cx);
+        if let Poll::Ready(None) = data {
+            self.in_flight = None;
+        }
+        data
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        HttpBody::size_hint(&self.inner)
+    }
+}
+
+struct Idle {
+    timeout: Duration,
+    timer: Pin<Box<Sleep>>,
+}
+
+impl Idle {
+    fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            timer: Box::pin(tokio::time::sleep(timeout)),
+        }
+    }
+}
+
+/// Start of the preface every HTTP/2 client sends first
+const HTTP2_PREFACE: &[u8] = b"PRI";
+
+/// Connection accepted by an [Incoming]
+pub(crate) struct Connection<C> {
+    inner: C,
+    idle: Option<Idle>,
+    /// Whether the first bytes were read, to tell HTTP/1 from HTTP/2
+    sniffed: bool,
+    requests: Requests,
+    _guard: ConnectionGuard,
+}
+
+impl<C> Connection<C> {
+    pub(crate) fn requests(&self) -> Requests {
+        self.requests.clone()
+    }
+
+    fn touch(&mut self) {
+        if let Some(idle) = &mut self.idle {
+            let deadline = Instant::now() + idle.timeout;
+            idle.timer
+                .as_mut()
+                .reset(deadline);
+        }
+    }
+
+    /// Stop the idle timeout once the connection turns out to be HTTP/2
+    fn sniff(&mut self, read: &[u8]) {
+        if self.sniffed || read.is_empty() {
+            return;
+        }
+
+        self.sniffed = true;
+        if read.starts_with(HTTP2_PREFACE) {
+            self.idle = None;
+        }
+    }
+
+    /// Whether the connection has been idle for longer than the idle timeout
+    fn expired(&mut self, cx: &mut Context<'_>) -> bool {
+        match &mut self.idle {
+            Some(idle) if self.requests.is_idle() => idle
+                .timer
+                .as_mut()
+                .poll(cx)
+                .is_ready(),
+            _ => false,
+        }
+    }
+}
+
+impl<C> AsyncRead for Connection<C>
+where
+    C: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        match Pin::new(&mut self.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                self.sniff(&buf.filled()[filled..]);
+                self.touch();
+                Poll::Ready(result)
+            }
+            // Reading nothing is an end of file, that closes an HTTP/1 connection gracefully
+            Poll::Pending if self.expired(cx) => Poll::Ready(Ok(())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<C> AsyncWrite for Connection<C>
+where
+    C: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let written = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if written.is_ready() {
+            self.touch();
+        }
+        written
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let written = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        if written.is_ready() {
+            self.touch();
+        }
+        written
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Fail reading the `body` with a `408 Request Timeout` once the whole body took longer than the
+/// `request_timeout`, or a chunk took longer than the `read_timeout`
+pub(crate) fn with_timeouts(
+    body: Body,
+    request_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+) -> Body {
+    if request_timeout.is_none() && read_timeout.is_none() {
+        return body;
+    }
+
+    Body::from_stream(Timed {
+        inner: body,
+        request: request_timeout.map(|timeout| Box::pin(tokio::time::sleep(timeout))),
+        read: read_timeout.map(Idle::new),
+        expired: false,
+    })
+}
+
+struct Timed {
+    inner: Body,
+    request: Option<Pin<Box<Sleep>>>,
+    read: Option<Idle>,
+    expired: bool,
+}
+
+impl Stream for Timed {
+    type Item = Result<Bytes, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.expired {
+            return Poll::Ready(None);
+        }
+
+        match Pin::new(&mut self.inner).poll_next(cx) {
+            Poll::Ready(chunk) => {
+                if let Some(read) = &mut self.read {
+                    let deadline = Instant::now() + read.timeout;
+                    read.timer
+                        .as_mut()
+                        .reset(deadline);
+                }
+                Poll::Ready(chunk)
+            }
+            Poll::Pending => {
+                let request_expired = self
+                    .request
+                    .as_mut()
+                    .is_some_and(|timer| {
+                        timer
+                            .as_mut()
+                            .poll(cx)
+                            .is_ready()
+                    });
+                let read_expired = self
+                    .read
+                    .as_mut()
+                    .is_some_and(|read| {
+                        read.timer
+                            .as_mut()
+                            .poll(cx)
+                            .is_ready()
+                    });
+
+                if request_expired || read_expired {
+                    self.expired = true;
+                    Poll::Ready(Some(Err(Error::new(
+                        "Timed out reading the body".into(),
+                        408,
+                    )
+                    .with_error_code("request_timeout")
+                    .into())))
+                } else {
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.expired {
+            true => (0, Some(0)),
+            false => (0, self.inner.size_hint().1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+
+    use crate::body::Body;
+
+    use super::with_timeouts;
+
+    fn slow_body(delays: &'static [u64]) -> Body {
+        Body::from_stream(futures::stream::iter(delays).then(|millis| async move {
+            tokio::time::sleep(Duration::from_millis(*millis)).await;
+            Ok::<_, std::io::Error>("chunk")
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout() {
+        let body = with_timeouts(
+            slow_body(&[10, 10, 10]),
+            None,
+            Some(Duration::from_millis(50)),
+        );
+        assert!(body.bytes().await.is_ok());
+
+        let body = with_timeouts(slow_body(&[10, 100]), None, Some(Duration::from_millis(50)));
+        assert_eq!(
+            body.bytes()
+                .await
+                .err()
+                .map(|err| *err.code()),
+            Some(408)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let body = with_timeouts(
+            slow_body(&[30, 30, 30]),
+            Some(Duration::from_millis(50)),
+            Some(Duration::from_millis(50)),
+        );
+        assert_eq!(
+            body.bytes()
+                .await
+                .err()
+                .map(|err| *err.code()),
+            Some(408)
+        );
+    }
+}
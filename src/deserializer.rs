@@ -1,27 +1,164 @@
+use bytes::Bytes;
+use futures::{Future, Stream};
 use serde::de::DeserializeOwned;
 
 use crate::{
+    body::Body,
     error::Error,
-    handler::{Json, StandardBodyType},
+    handler::{Form, Json, StandardBodyType},
+    request::HeaderMap,
     result::InternalResult,
 };
 
+/// Describes how to convert a [Body](crate::body::Body) into the `Item` received by the handler
+///
+/// The [Body] may still be streaming, so each deserializer decides whether to buffer it, using
+/// [Body::bytes](crate::body::Body::bytes), or to consume it chunk by chunk
+///
+/// A deserializer that declares [media types](BodyDeserializer::media_types) needs a body of one
+/// of them. Before reaching the deserializer, a request with a body but without a matching
+/// `Content-Type` is answered with a `415 Unsupported Media Type`, and one without a body with a
+/// `400 Bad Request`, just like an empty body. Deserializers without media types, like [String]
+/// and [Bytes], accept any body, even an empty one
 pub trait BodyDeserializer {
     type Item: DeserializeOwned;
 
-    fn deserialize(content: &StandardBodyType) -> InternalResult<Self::Item>
+    fn deserialize(
+        content: StandardBodyType,
+    ) -> impl Future<Output = InternalResult<Self::Item>> + Send
     where
         Self: std::marker::Sized;
+
+    /// Media types accepted by the deserializer, like `application/json`
+    ///
+    /// A media type can use a wildcard as the subtype, like `text/*`, or as the subtype before a
+    /// structured syntax suffix, like `application/*+json`. No media types means any
+    /// `Content-Type` is accepted, and so is a request without one or without a body
+    fn media_types() -> &'static [&'static str] {
+        &[]
+    }
+}
+
+/// Check the `Content-Type` of the request against the [media
+/// types](BodyDeserializer::media_types) of the `Deserializer`
+pub(crate) fn check_content_type<Deserializer: BodyDeserializer>(
+    headers: &HeaderMap,
+    body: &Body,
+) -> InternalResult<()> {
+    let media_types = Deserializer::media_types();
+    if media_types.is_empty() {
+        return Ok(());
+    }
+
+    let Some(content_type) = headers.get(http::header::CONTENT_TYPE) else {
+        if !has_body(body) {
+            return Err(missing_body());
+        }
+
+        return Err(unsupported_media_type(format!(
+            "Missing Content-Type, expected one of: {}",
+            media_types.join(", ")
+        )));
+    };
+
+    let essence = content_type
+        .to_str()
+        .ok()
+        .and_then(|content_type| content_type.split(';').next())
+        .map(str::trim)
+        .unwrap_or_default();
+
+    if media_types
+        .iter()
+        .any(|media_type| matches_media_type(media_type, essence))
+    {
+        return Ok(());
+    }
+
+    Err(unsupported_media_type(format!(
+        "Unsupported Content-Type `{}`, expected one of: {}",
+        essence,
+        media_types.join(", ")
+    )))
+}
+
+/// Whether the request has a body, whatever its headers say
+///
+/// A streaming [Body] may still produce chunks, unless it already knows it's over, like the body
+/// of a request without one
+fn has_body(body: &Body) -> bool {
+    match body.as_bytes() {
+        Some(bytes) => !bytes.is_empty(),
+        None => body.size_hint().1 != Some(0),
+    }
+}
+
+fn unsupported_media_type(message: String) -> Error {
+    Error::new(message, 415).with_error_code("unsupported_media_type")
+}
+
+fn matches_media_type(pattern: &str, media_type: &str) -> bool {
+    let (Some((pattern_type, pattern_subtype)), Some((kind, subtype))) =
+        (pattern.split_once('/'), media_type.split_once('/'))
+    else {
+        return false;
+    };
+
+    if !pattern_type.eq_ignore_ascii_case(kind) {
+        return false;
+    }
+
+    match pattern_subtype.strip_prefix('*') {
+        Some(suffix) => subtype
+            .to_ascii_lowercase()
+            .ends_with(&suffix.to_ascii_lowercase()),
+        None => pattern_subtype.eq_ignore_ascii_case(subtype),
+    }
+}
+
+/// Error for a deserializer that needs a body, but the request has none
+fn missing_body() -> Error {
+    Error::new("Missing request body".into(), 400).with_error_code("missing_body")
+}
+
+/// Read the whole body of a deserializer with media types, that can't be empty
+async fn non_empty_bytes(content: StandardBodyType) -> InternalResult<Bytes> {
+    let bytes = content.bytes().await?;
+    if bytes.is_empty() {
+        return Err(missing_body());
+    }
+
+    Ok(bytes)
 }
 
 impl BodyDeserializer for String {
     type Item = String;
 
-    fn deserialize(_content: &StandardBodyType) -> InternalResult<Self::Item>
+    #[allow(clippy::manual_async_fn)]
+    fn deserialize(
+        content: StandardBodyType,
+    ) -> impl Future<Output = InternalResult<Self::Item>> + Send
     where
         Self: std::marker::Sized,
     {
-        Ok(_content.to_owned())
+        async move {
+            let bytes = content.bytes().await?;
+            String::from_utf8(bytes.to_vec())
+                .map_err(|err| Error::new(format!("Invalid UTF-8 body: {}", err), 400))
+        }
+    }
+}
+
+impl BodyDeserializer for Bytes {
+    type Item = Bytes;
+
+    fn deserialize(
+        content: StandardBodyType,
+    ) -> impl Future<Output = InternalResult<Self::Item>> + Send
+    where
+        Self: std::marker::Sized,
+    {
+        content.bytes()
     }
 }
 
@@ -31,10 +168,333 @@ where
 {
     type Item = T;
 
-    fn deserialize(content: &StandardBodyType) -> InternalResult<Self::Item>
+    #[allow(clippy::manual_async_fn)]
+    fn deserialize(
+        content: StandardBodyType,
+    ) -> impl Future<Output = InternalResult<Self::Item>> + Send
+    where
+        Self: std::marker::Sized,
+    {
+        async move {
+            let bytes = non_empty_bytes(content).await?;
+            serde_json::from_slice(&bytes).map_err(|err| Error::new(err.to_string(), 422))
+        }
+    }
+
+    fn media_types() -> &'static [&'static str] {
+        &["application/json", "application/*+json"]
+    }
+}
+
+impl<T> BodyDeserializer for Form<T>
+where
+    T: DeserializeOwned,
+{
+    type Item = T;
+
+    #[allow(clippy::manual_async_fn)]
+    fn deserialize(
+        content: StandardBodyType,
+    ) -> impl Future<Output = InternalResult<Self::Item>> + Send
+    where
+        Self: std::marker::Sized,
+    {
+        async move {
+            let bytes = non_empty_bytes(content).await?;
+            serde_urlencoded::from_bytes(&bytes).map_err(|err| Error::new(err.to_string(), 422))
+        }
+    }
+
+    fn media_types() -> &'static [&'static str] {
+        &["application/x-www-form-urlencoded"]
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl<T> BodyDeserializer for crate::handler::MsgPack<T>
+where
+    T: DeserializeOwned,
+{
+    type Item = T;
+
+    #[allow(clippy::manual_async_fn)]
+    fn deserialize(
+        content: StandardBodyType,
+    ) -> impl Future<Output = InternalResult<Self::Item>> + Send
+    where
+        Self: std::marker::Sized,
+    {
+        async move {
+            let bytes = non_empty_bytes(content).await?;
+            rmp_serde::from_slice(&bytes).map_err(|err| Error::new(err.to_string(), 422))
+        }
+    }
+
+    fn media_types() -> &'static [&'static str] {
+        &[
+            "application/msgpack",
+            "application/x-msgpack",
+            "application/vnd.msgpack",
+        ]
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl<T> BodyDeserializer for crate::handler::Cbor<T>
+where
+    T: DeserializeOwned,
+{
+    type Item = T;
+
+    #[allow(clippy::manual_async_fn)]
+    fn deserialize(
+        content: StandardBodyType,
+    ) -> impl Future<Output = InternalResult<Self::Item>> + Send
     where
         Self: std::marker::Sized,
     {
-        serde_json::from_str(content).map_err(|err| Error::new(err.to_string(), 422))
+        async move {
+            let bytes = non_empty_bytes(content).await?;
+            ciborium::from_reader(&bytes[..]).map_err(|err| Error::new(err.to_string(), 422))
+        }
+    }
+
+    fn media_types() -> &'static [&'static str] {
+        &["application/cbor", "application/*+cbor"]
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl<T> BodyDeserializer for crate::handler::Yaml<T>
+where
+    T: DeserializeOwned,
+{
+    type Item = T;
+
+    #[allow(clippy::manual_async_fn)]
+    fn deserialize(
+        content: StandardBodyType,
+    ) -> impl Future<Output = InternalResult<Self::Item>> + Send
+    where
+        Self: std::marker::Sized,
+    {
+        async move {
+            let bytes = non_empty_bytes(content).await?;
+            serde_norway::from_slice(&bytes).map_err(|err| Error::new(err.to_string(), 422))
+        }
+    }
+
+    fn media_types() -> &'static [&'static str] {
+        &[
+            "application/yaml",
+            "application/x-yaml",
+            "text/yaml",
+            "application/*+yaml",
+        ]
+    }
+}
+
+#[cfg(feature = "toml")]
+impl<T> BodyDeserializer for crate::handler::Toml<T>
+where
+    T: DeserializeOwned,
+{
+    type Item = T;
+
+    #[allow(clippy::manual_async_fn)]
+    fn deserialize(
+        content: StandardBodyType,
+    ) -> impl Future<Output = InternalResult<Self::Item>> + Send
+    where
+        Self: std::marker::Sized,
+    {
+        async move {
+            let bytes = non_empty_bytes(content).await?;
+            std::str::from_utf8(&bytes)
+                .map_err(|err| Error::new(format!("Invalid UTF-8 body: {}", err), 400))
+                .and_then(|body| {
+                    toml::from_str(body).map_err(|err| Error::new(err.to_string(), 422))
+                })
+        }
+    }
+
+    fn media_types() -> &'static [&'static str] {
+        &["application/toml"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{header::CONTENT_TYPE, HeaderMap};
+
+    use crate::{
+        body::Body,
+        handler::{Form, Json},
+    };
+
+    use super::{check_content_type, BodyDeserializer};
+
+    fn headers(content_type: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_accepted_content_types() {
+        let body = Body::from("1");
+
+        assert!(check_content_type::<Json<u32>>(&headers("application/json"), &body).is_ok());
+        assert!(check_content_type::<Json<u32>>(
+            &headers("application/json; charset=utf-8"),
+            &body
+        )
+        .is_ok());
+        assert!(
+            check_content_type::<Json<u32>>(&headers("application/merge-patch+json"), &body)
+                .is_ok()
+        );
+        assert!(check_content_type::<String>(&headers("image/png"), &body).is_ok());
+        assert!(check_content_type::<String>(&HeaderMap::new(), &Body::empty()).is_ok());
+    }
+
+    #[test]
+    fn test_missing_content_type() {
+        let err = check_content_type::<Json<u32>>(&HeaderMap::new(), &Body::empty()).unwrap_err();
+        assert_eq!(err.code(), &400);
+
+        let err = check_content_type::<Json<u32>>(&HeaderMap::new(), &hyper::Body::empty().into())
+            .unwrap_err();
+        assert_eq!(err.code(), &400);
+
+        let err =
+            check_content_type::<Json<u32>>(&HeaderMap::new(), &Body::from("12345")).unwrap_err();
+        assert_eq!(err.code(), &415);
+        assert_eq!(err.error_code(), Some("unsupported_media_type"));
+    }
+
+    #[test]
+    fn test_missing_content_type_with_streamed_body() {
+        // Neither a Content-Length nor a Transfer-Encoding, like an HTTP/2 request
+        let (_sender, body) = hyper::Body::channel();
+        let err = check_content_type::<Json<u32>>(&HeaderMap::new(), &body.into()).unwrap_err();
+        assert_eq!(err.code(), &415);
+
+        let body = Body::from_stream(futures::stream::iter([Ok::<_, std::io::Error>("12345")]));
+        let err = check_content_type::<Json<u32>>(&HeaderMap::new(), &body).unwrap_err();
+        assert_eq!(err.code(), &415);
+    }
+
+    #[test]
+    fn test_unsupported_content_type() {
+        let err =
+            check_content_type::<Json<u32>>(&headers("text/plain"), &Body::from("1")).unwrap_err();
+
+        assert_eq!(err.code(), &415);
+        assert_eq!(err.error_code(), Some("unsupported_media_type"));
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Login {
+        username: String,
+        remember: bool,
+    }
+
+    #[tokio::test]
+    async fn test_form() {
+        let login = Form::<Login>::deserialize(Body::from("username=yahf&remember=true"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            login,
+            Login {
+                username: "yahf".into(),
+                remember: true
+            }
+        );
+
+        let err = Form::<Login>::deserialize(Body::from("username=yahf"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), &422);
+    }
+
+    #[cfg(any(
+        feature = "msgpack",
+        feature = "cbor",
+        feature = "yaml",
+        feature = "toml"
+    ))]
+    #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+    struct Upload {
+        name: String,
+        size: u64,
+        tags: Vec<String>,
+    }
+
+    /// Serialize an [Upload] with `Format` and deserialize it back
+    #[cfg(any(
+        feature = "msgpack",
+        feature = "cbor",
+        feature = "yaml",
+        feature = "toml"
+    ))]
+    async fn round_trip<Format>()
+    where
+        Format: BodyDeserializer<Item = Upload> + crate::serializer::BodySerializer<Item = Upload>,
+    {
+        let upload = || Upload {
+            name: "yahf.tar.gz".into(),
+            size: 4096,
+            tags: vec!["release".into()],
+        };
+
+        let body = Format::serialize(upload()).unwrap();
+        assert_eq!(
+            Format::deserialize(body)
+                .await
+                .unwrap(),
+            upload()
+        );
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn test_msgpack() {
+        round_trip::<crate::handler::MsgPack<Upload>>().await;
+    }
+
+    #[cfg(feature = "cbor")]
+    #[tokio::test]
+    async fn test_cbor() {
+        round_trip::<crate::handler::Cbor<Upload>>().await;
+    }
+
+    #[cfg(feature = "yaml")]
+    #[tokio::test]
+    async fn test_yaml() {
+        round_trip::<crate::handler::Yaml<Upload>>().await;
+        assert!(check_content_type::<crate::handler::Yaml<Upload>>(
+            &headers("text/yaml"),
+            &Body::from("name: yahf")
+        )
+        .is_ok());
+    }
+
+    #[cfg(feature = "toml")]
+    #[tokio::test]
+    async fn test_toml() {
+        round_trip::<crate::handler::Toml<Upload>>().await;
+    }
+
+    #[tokio::test]
+    async fn test_missing_body() {
+        let err = Json::<u32>::deserialize(Body::empty())
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code(), &400);
+        assert_eq!(err.error_code(), Some("missing_body"));
     }
 }
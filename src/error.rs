@@ -1,23 +1,118 @@
-use crate::response::Response;
+//! Error returned by [`handlers`](crate::handler::Runner) and [`middlewares`](crate::middleware)
+//!
+//! Refeer to the [Error] for more information
 
+use std::{fmt::Display, sync::Arc};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{accept, body::Body, response::Response};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Error answered with a status `code` and a message
+///
+/// Besides the message, an [Error] can carry a machine readable code, structured details and
+/// the error that caused it:
+///
+/// ```rust
+/// # use serde::Serialize;
+/// use yahf::error::Error;
+///
+/// #[derive(Serialize)]
+/// struct Field { name: &'static str }
+///
+/// let error = Error::new("Invalid user".into(), 422)
+///     .with_error_code("invalid_user")
+///     .with_details(vec![Field { name: "email" }])
+///     .with_source(std::io::Error::other("connection reset"));
+/// ```
+///
+/// How the [Error] is written on the response is decided by an [ErrorRenderer]. Only the
+/// [Server](crate::server::Server) turns an [Error] into a response, so a handler or a middleware
+/// that wants a different one returns the [Response] it wants instead of the [Error]
 #[derive(Debug)]
 pub struct Error {
     body: String,
     code: u16,
+    error_code: Option<String>,
+    details: Option<Value>,
+    source: Option<BoxError>,
 }
 
 impl Error {
+    /// Create a new [Error] with a message and a status `code`
     pub fn new(body: String, code: u16) -> Self {
-        Self { body, code }
+        Self {
+            body,
+            code,
+            error_code: None,
+            details: None,
+            source: None,
+        }
+    }
+
+    /// Set a machine readable code, like `user_not_found`
+    pub fn with_error_code(mut self, error_code: impl Into<String>) -> Self {
+        self.error_code = Some(error_code.into());
+        self
+    }
+
+    /// Set structured details about the error
+    ///
+    /// Details that fail to serialize are ignored
+    pub fn with_details(mut self, details: impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+
+    /// Set the error that caused this one
+    ///
+    /// The source is never written on the response, it's only available through
+    /// [`source`](std::error::Error::source)
+    pub fn with_source(mut self, source: impl Into<BoxError>) -> Self {
+        self.source = Some(source.into());
+        self
     }
 
+    /// Message of the [Error]
     pub fn body(&self) -> &String {
         &self.body
     }
 
+    /// Status code of the [Error]
     pub fn code(&self) -> &u16 {
         &self.code
     }
+
+    /// Machine readable code of the [Error]
+    pub fn error_code(&self) -> Option<&str> {
+        self.error_code.as_deref()
+    }
+
+    /// Structured details of the [Error]
+    pub fn details(&self) -> Option<&Value> {
+        self.details.as_ref()
+    }
+
+    fn status(&self) -> http::StatusCode {
+        http::StatusCode::from_u16(self.code).unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.body)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn std::error::Error + 'static))
+    }
 }
 
 impl From<http::Error> for Error {
@@ -26,26 +121,149 @@ impl From<http::Error> for Error {
     }
 }
 
-impl From<Error> for Response<String> {
-    fn from(val: Error) -> Self {
-        Response::builder()
-            .status(val.code)
-            .body(val.body)
-            .map_or_else(
-                |err| {
-                    http::Response::builder()
-                        .status(500)
-                        .body(err.to_string())
-                        .expect("Error creating the error")
-                },
-                |res| res,
-            )
+/// Write an [Error] on the body of a response
+pub trait ErrorRenderer: Send + Sync + 'static {
+    /// Media type of the rendered body, used to match the `Accept` header and as the
+    /// `Content-Type`
+    fn media_type(&self) -> &'static str;
+
+    /// Render the [Error] as the body of the response
+    fn render(&self, error: &Error) -> Body;
+}
+
+/// Render the message of the [Error] as `text/plain`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlainTextRenderer;
+
+impl ErrorRenderer for PlainTextRenderer {
+    fn media_type(&self) -> &'static str {
+        "text/plain; charset=utf-8"
+    }
+
+    fn render(&self, error: &Error) -> Body {
+        error.body.clone().into()
+    }
+}
+
+/// Render the [Error] as `application/json`, like
+/// `{"code": "user_not_found", "message": "User not found", "details": {...}}`
+///
+/// `code` and `details` are omitted when they aren't set
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonRenderer;
+
+impl ErrorRenderer for JsonRenderer {
+    fn media_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn render(&self, error: &Error) -> Body {
+        let mut object = Map::new();
+        if let Some(error_code) = &error.error_code {
+            object.insert("code".into(), error_code.clone().into());
+        }
+        object.insert("message".into(), error.body.clone().into());
+        if let Some(details) = &error.details {
+            object.insert("details".into(), details.clone());
+        }
+
+        Value::Object(object)
+            .to_string()
             .into()
     }
 }
 
+/// Render the [Error] as a [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) problem
+/// details, with `application/problem+json`
+///
+/// The message goes on `detail`, while the machine readable code and the details are added as
+/// the `code` and `details` extension members
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProblemJsonRenderer;
+
+impl ErrorRenderer for ProblemJsonRenderer {
+    fn media_type(&self) -> &'static str {
+        "application/problem+json"
+    }
+
+    fn render(&self, error: &Error) -> Body {
+        let status = error.status();
+
+        let mut object = Map::new();
+        object.insert("type".into(), "about:blank".into());
+        object.insert(
+            "title".into(),
+            status
+                .canonical_reason()
+                .unwrap_or_default()
+                .into(),
+        );
+        object.insert("status".into(), status.as_u16().into());
+        object.insert("detail".into(), error.body.clone().into());
+        if let Some(error_code) = &error.error_code {
+            object.insert("code".into(), error_code.clone().into());
+        }
+        if let Some(details) = &error.details {
+            object.insert("details".into(), details.clone());
+        }
+
+        Value::Object(object)
+            .to_string()
+            .into()
+    }
+}
+
+/// [ErrorRenderers](ErrorRenderer) of a [Server](crate::server::Server), in order of
+/// preference
+#[derive(Clone)]
+pub(crate) struct ErrorRenderers(Vec<Arc<dyn ErrorRenderer>>);
+
+impl Default for ErrorRenderers {
+    fn default() -> Self {
+        Self(vec![
+            Arc::new(PlainTextRenderer),
+            Arc::new(JsonRenderer),
+            Arc::new(ProblemJsonRenderer),
+        ])
+    }
+}
+
+impl ErrorRenderers {
+    pub(crate) fn only(renderer: impl ErrorRenderer) -> Self {
+        Self(vec![Arc::new(renderer)])
+    }
+
+    /// Render the [Error] with the renderer preferred by the `accept` header, falling back to
+    /// the first one when none is acceptable
+    pub(crate) fn render(&self, error: Error, accept: Option<&str>) -> Response<Body> {
+        let media_types: Vec<_> = self
+            .0
+            .iter()
+            .map(|renderer| renderer.media_type())
+            .collect();
+        let renderer = &self.0[accept::preferred(accept, &media_types).unwrap_or(0)];
+
+        let mut response = http::Response::builder()
+            .status(error.status())
+            .header(http::header::CONTENT_TYPE, renderer.media_type())
+            .body(renderer.render(&error))
+            .expect("A valid status and media type");
+        // A `406 Not Acceptable` only happens because of the `Accept` header, even with a single
+        // renderer
+        if self.0.len() > 1 || error.status() == http::StatusCode::NOT_ACCEPTABLE {
+            accept::vary(response.headers_mut());
+        }
+
+        response.into()
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::error::Error as _;
+
+    use serde_json::json;
+
     use super::*;
 
     #[test]
@@ -58,4 +276,72 @@ mod tests {
 
         assert!(error.code() == &400);
     }
+
+    #[test]
+    fn test_error_chaining() {
+        let error = Error::new("Failed to load".into(), 500)
+            .with_source(std::io::Error::other("connection reset"));
+
+        assert_eq!(
+            error
+                .source()
+                .map(|source| source.to_string()),
+            Some("connection reset".into())
+        );
+    }
+
+    async fn rendered(error: Error, accept: Option<&str>) -> (String, String) {
+        let response = ErrorRenderers::default().render(error, accept);
+        let content_type = response.headers()[http::header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = response
+            .into_body()
+            .bytes()
+            .await
+            .unwrap();
+
+        (content_type, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_render_plain_text_by_default() {
+        let (content_type, body) = rendered(Error::new("Not found".into(), 404), None).await;
+
+        assert_eq!(content_type, "text/plain; charset=utf-8");
+        assert_eq!(body, "Not found");
+    }
+
+    #[tokio::test]
+    async fn test_render_json() {
+        let error = Error::new("Invalid user".into(), 422)
+            .with_error_code("invalid_user")
+            .with_details(json!({"field": "email"}));
+        let (content_type, body) = rendered(error, Some("application/json")).await;
+
+        assert_eq!(content_type, "application/json");
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!({"code": "invalid_user", "message": "Invalid user", "details": {"field": "email"}})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_problem_json() {
+        let error = Error::new("User 1 not found".into(), 404).with_error_code("user_not_found");
+        let (content_type, body) = rendered(error, Some("application/problem+json")).await;
+
+        assert_eq!(content_type, "application/problem+json");
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!({
+                "type": "about:blank",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "User 1 not found",
+                "code": "user_not_found"
+            })
+        );
+    }
 }
@@ -0,0 +1,142 @@
+//! Storage for the fallbacks set on a [Router](crate::router::Router) or a
+//! [Server](crate::server::Server)
+
+use crate::{
+    handler::{BoxedHandler, RefHandler},
+    path::PathParams,
+};
+
+/// Fallbacks set with `fallback`, each one scoped to the `prefix` the
+/// [Router](crate::router::Router) was nested under
+#[derive(Clone, Default)]
+pub struct Fallbacks(Vec<(String, BoxedHandler)>);
+
+impl Fallbacks {
+    /// Set the fallback of the root, replacing the previous one
+    pub(crate) fn set(&mut self, handler: BoxedHandler) {
+        self.0
+            .retain(|(prefix, _)| !prefix.is_empty());
+        self.0
+            .push((String::new(), handler));
+    }
+
+    /// Scope every fallback of `another` under `prefix`, keeping the ones already set for the
+    /// same prefix
+    pub(crate) fn nest(
+        &mut self,
+        prefix: &str,
+        another: Fallbacks,
+        f: &impl Fn(BoxedHandler) -> BoxedHandler,
+    ) {
+        let prefix = prefix.trim_end_matches('/');
+
+        another
+            .0
+            .into_iter()
+            .map(|(nested_prefix, handler)| (format!("{}{}", prefix, nested_prefix), handler))
+            .for_each(|(prefix, handler)| {
+                if !self
+                    .0
+                    .iter()
+                    .any(|(existing, _)| *existing == prefix)
+                {
+                    self.0
+                        .push((prefix, f(handler)));
+                }
+            });
+    }
+
+    /// Find the fallback with the most specific prefix that matches the `path`
+    pub(crate) fn get(&self, path: &str) -> Option<(RefHandler<'_>, PathParams)> {
+        self.0
+            .iter()
+            .filter_map(|(prefix, handler)| {
+                match_prefix(prefix, path).map(|(size, params)| (size, handler, params))
+            })
+            .max_by_key(|(size, _, _)| *size)
+            .map(|(_, handler, params)| (handler.as_ref(), PathParams::new(params)))
+    }
+}
+
+/// Match the segments of `prefix` against the start of `path`, returning the number of matched
+/// segments and the values of the `{param}` segments
+fn match_prefix(prefix: &str, path: &str) -> Option<(usize, Vec<(String, String)>)> {
+    let mut path_segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty());
+    let mut params = Vec::new();
+    let mut size = 0;
+
+    for segment in prefix
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+    {
+        let value = path_segments.next()?;
+        if segment.starts_with('{') && segment.ends_with('}') {
+            params.push((segment[1..segment.len() - 1].to_string(), value.to_string()));
+        } else if segment != value {
+            return None;
+        }
+
+        size += 1;
+    }
+
+    Some((size, params))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        handler::{encapsulate_runner, BoxedHandler},
+        path::PathParams,
+    };
+
+    use super::Fallbacks;
+
+    fn handler() -> BoxedHandler {
+        Box::new(encapsulate_runner(
+            || async { String::new() },
+            &(),
+            &String::with_capacity(0),
+        ))
+    }
+
+    #[test]
+    fn test_most_specific_prefix_wins() {
+        let mut nested = Fallbacks::default();
+        nested.set(handler());
+
+        let mut fallbacks = Fallbacks::default();
+        fallbacks.set(handler());
+        fallbacks.nest("/orgs/{org}/", nested, &|handler| handler);
+
+        let (_, params) = fallbacks
+            .get("/orgs/yahf/missing")
+            .unwrap();
+        assert_eq!(params, PathParams::new(vec![("org".into(), "yahf".into())]));
+
+        let (_, params) = fallbacks
+            .get("/missing")
+            .unwrap();
+        assert_eq!(params, PathParams::default());
+    }
+
+    #[test]
+    fn test_nested_fallback_is_scoped_to_its_prefix() {
+        let mut nested = Fallbacks::default();
+        nested.set(handler());
+
+        let mut fallbacks = Fallbacks::default();
+        fallbacks.nest("/api", nested, &|handler| handler);
+
+        assert!(fallbacks
+            .get("/api/missing")
+            .is_some());
+        assert!(fallbacks
+            .get("/api")
+            .is_some());
+        assert!(fallbacks
+            .get("/other")
+            .is_none());
+    }
+}
@@ -1,41 +1,81 @@
 //! Async functions that are used to handle requests
 
-use std::{marker::PhantomData, pin::Pin};
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+};
 
 use futures::Future;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::Serialize;
 
 use crate::{
-    deserializer::BodyDeserializer, request::Request, response::Response, result::InternalResult,
-    runner_input::RunnerInput, runner_output::RunnerOutput, serializer::BodySerializer,
+    body::Body,
+    request::Request,
+    response::Response,
+    result::InternalResult,
+    runner_input::{RequiredState, RunnerInput, RunnerInputParts},
+    runner_output::RunnerOutput,
+    serializer::BodySerializer,
 };
 
-pub(crate) type StandardBodyType = String;
+pub(crate) type StandardBodyType = Body;
 pub(crate) type GenericRequest = Request<StandardBodyType>;
 pub(crate) type GenericResponse = Response<StandardBodyType>;
 pub(crate) type BoxedHandler = Box<dyn BoxedRunner>;
-pub(crate) type RefHandler<'a> = &'a (dyn BoxedRunner);
+pub(crate) type RefHandler<'a> = &'a dyn BoxedRunner;
+pub(crate) type BoxedHandlerFuture =
+    Pin<Box<dyn Future<Output = InternalResult<GenericResponse>> + Send>>;
 
 /// An trait to mark functions handler
 ///
 /// To accept new types of handler just impl this trait.
-/// All implementations from this crate are using the signature `(Type, BodyDeserializer)` for both generic parameters
+/// All implementations from this crate are using the signature `(Type, BodyDeserializer)` for both generic parameters,
+/// where the `Type` of the input is [Extractors] with the types of the arguments
+///
+/// Handlers can receive up to 12 arguments. Every argument, except the last one, is extracted from
+/// the request [Parts](crate::request::Parts) using
+/// [RunnerInputParts](crate::runner_input::RunnerInputParts), while the last one is the only one
+/// that can consume the body, using [RunnerInput]:
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # use yahf::handler::{Json, Path, Query};
+/// # use yahf::router::Router;
+/// # #[derive(Deserialize)]
+/// # struct Filter { active: bool }
+/// # #[derive(Deserialize)]
+/// # struct Create { name: String }
+/// async fn handler(Path(id): Path<u64>, filter: Query<Filter>, body: Create) -> String
+/// # { body.name }
+///
+/// let router = Router::new().post(
+///     "/orgs/{id}/users",
+///     handler,
+///     &Json::new(),
+///     &String::with_capacity(0),
+/// );
+/// ```
 pub trait Runner<Input, Output>: Clone + Send + Sync {
+    /// Run the handler with the [Request], returning the generated [Response]
     fn call_runner(
         &'_ self,
         run: InternalResult<Request<StandardBodyType>>,
-    ) -> impl Future<Output = InternalResult<Response<String>>> + Send + '_;
+    ) -> impl Future<Output = InternalResult<Response<StandardBodyType>>> + Send + '_;
+
+    /// States the arguments of the handler can't be extracted without
+    fn required_states() -> Vec<RequiredState> {
+        Vec::new()
+    }
 }
 
-impl<ReqBody, ResBody, FnIn, FnOut, BodyDes, BodySer, Fut, F>
-    Runner<(FnIn, BodyDes), (FnOut, BodySer)> for F
+impl<ResBody, FnIn, FnOut, Marker, BodyDes, BodySer, Fut, F>
+    Runner<(Extractors<(FnIn,)>, BodyDes), (Returns<FnOut, Marker>, BodySer)> for F
 where
     F: Fn(FnIn) -> Fut + Send + Sync + Clone,
     Fut: Future<Output = FnOut> + Send,
     FnIn: RunnerInput<BodyDes> + Send,
-    BodyDes: BodyDeserializer<Item = ReqBody>,
-    ReqBody: DeserializeOwned,
-    FnOut: RunnerOutput<BodySer>,
+    FnOut: RunnerOutput<BodySer, Marker>,
     BodySer: BodySerializer<Item = ResBody>,
     ResBody: Serialize,
 {
@@ -43,23 +83,31 @@ where
     fn call_runner(
         &'_ self,
         inp: InternalResult<Request<StandardBodyType>>,
-    ) -> impl Future<Output = InternalResult<Response<String>>> + Send + '_ {
+    ) -> impl Future<Output = InternalResult<Response<StandardBodyType>>> + Send + '_ {
         async move {
-            let inp = FnIn::try_into(inp);
+            let accept = accept(&inp);
+            let inp = FnIn::try_into(inp).await;
 
             match inp {
-                Ok(req) => FnOut::try_into(self(req).await),
+                Ok(req) => FnOut::try_into(self(req).await, accept.as_deref()),
                 Err(err) => Err(err),
             }
         }
     }
+
+    fn required_states() -> Vec<RequiredState> {
+        <FnIn as RunnerInput<BodyDes>>::required_state()
+            .into_iter()
+            .collect()
+    }
 }
 
-impl<ResBody, FnOut, BodySer, Fut, F> Runner<((), ()), (FnOut, BodySer)> for F
+impl<ResBody, FnOut, Marker, BodySer, Fut, F> Runner<((), ()), (Returns<FnOut, Marker>, BodySer)>
+    for F
 where
     F: Fn() -> Fut + Send + Sync + Clone,
     Fut: Future<Output = FnOut> + Send,
-    FnOut: RunnerOutput<BodySer>,
+    FnOut: RunnerOutput<BodySer, Marker>,
     BodySer: BodySerializer<Item = ResBody>,
     ResBody: Serialize,
 {
@@ -67,17 +115,90 @@ where
     fn call_runner(
         &'_ self,
         _run: InternalResult<Request<StandardBodyType>>,
-    ) -> impl Future<Output = InternalResult<Response<String>>> + Send + '_ {
+    ) -> impl Future<Output = InternalResult<Response<StandardBodyType>>> + Send + '_ {
         async move {
+            let accept = accept(&_run);
             _run?;
-            FnOut::try_into(self().await)
+            FnOut::try_into(self().await, accept.as_deref())
         }
     }
 }
 
+/// `Accept` header of the request, used to negotiate the format of the response
+fn accept<B>(req: &InternalResult<Request<B>>) -> Option<String> {
+    req.as_ref()
+        .ok()
+        .and_then(|req| {
+            req.headers()
+                .get(http::header::ACCEPT)
+        })
+        .and_then(|accept| accept.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Marker used by [Runner] to identify the arguments of a handler
+pub struct Extractors<T>(PhantomData<T>);
+
+/// Marker used by [Runner] to identify the return type of a handler, along with the marker of
+/// its [RunnerOutput]
+pub struct Returns<T, Marker>(PhantomData<(T, Marker)>);
+
+macro_rules! impl_runner_for_fn {
+    ($($parts: ident),+) => {
+        #[allow(non_snake_case)]
+        impl<$($parts,)+ ResBody, FnIn, FnOut, Marker, BodyDes, BodySer, Fut, F>
+            Runner<(Extractors<($($parts,)+ FnIn)>, BodyDes), (Returns<FnOut, Marker>, BodySer)> for F
+        where
+            F: Fn($($parts,)+ FnIn) -> Fut + Send + Sync + Clone,
+            Fut: Future<Output = FnOut> + Send,
+            $($parts: RunnerInputParts + Send,)+
+            FnIn: RunnerInput<BodyDes> + Send,
+            FnOut: RunnerOutput<BodySer, Marker>,
+            BodySer: BodySerializer<Item = ResBody>,
+            ResBody: Serialize,
+        {
+            #[allow(clippy::manual_async_fn)]
+            fn call_runner(
+                &'_ self,
+                inp: InternalResult<Request<StandardBodyType>>,
+            ) -> impl Future<Output = InternalResult<Response<StandardBodyType>>> + Send + '_ {
+                async move {
+                    let accept = accept(&inp);
+                    let (parts, body) = inp?.into_parts();
+                    $(let $parts = $parts::try_from_parts(&parts)?;)+
+                    let inp = FnIn::try_into(Ok(Request::from_parts(parts, body))).await?;
+
+                    FnOut::try_into(self($($parts,)+ inp).await, accept.as_deref())
+                }
+            }
+
+            fn required_states() -> Vec<RequiredState> {
+                [$(<$parts as RunnerInputParts>::required_state(),)+ <FnIn as RunnerInput<BodyDes>>::required_state()]
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            }
+        }
+    };
+}
+
+impl_runner_for_fn!(P1);
+impl_runner_for_fn!(P1, P2);
+impl_runner_for_fn!(P1, P2, P3);
+impl_runner_for_fn!(P1, P2, P3, P4);
+impl_runner_for_fn!(P1, P2, P3, P4, P5);
+impl_runner_for_fn!(P1, P2, P3, P4, P5, P6);
+impl_runner_for_fn!(P1, P2, P3, P4, P5, P6, P7);
+impl_runner_for_fn!(P1, P2, P3, P4, P5, P6, P7, P8);
+impl_runner_for_fn!(P1, P2, P3, P4, P5, P6, P7, P8, P9);
+impl_runner_for_fn!(P1, P2, P3, P4, P5, P6, P7, P8, P9, P10);
+impl_runner_for_fn!(P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11);
+
+/// Marker to [serialize](crate::serializer::BodySerializer) and [deserialize](crate::deserializer::BodyDeserializer) bodies as JSON
 pub struct Json<T>(PhantomData<T>);
 
 impl<T> Json<T> {
+    /// Create a new [Json] marker
     #[allow(dead_code)]
     pub fn new() -> Self {
         Self(PhantomData)
@@ -90,43 +211,366 @@ impl<T> Default for Json<T> {
     }
 }
 
+/// Marker to [serialize](crate::serializer::BodySerializer) and [deserialize](crate::deserializer::BodyDeserializer) bodies as `application/x-www-form-urlencoded`
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # use yahf::handler::Form;
+/// # use yahf::router::Router;
+/// #[derive(Deserialize)]
+/// struct Login { username: String, password: String }
+///
+/// async fn login(login: Login) -> String
+/// # { login.username }
+///
+/// let router = Router::new().post("/login", login, &Form::new(), &String::with_capacity(0));
+/// ```
+pub struct Form<T>(PhantomData<T>);
+
+impl<T> Form<T> {
+    /// Create a new [Form] marker
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Default for Form<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Marker to [serialize](crate::serializer::BodySerializer) and [deserialize](crate::deserializer::BodyDeserializer) bodies as [MessagePack](https://msgpack.org), with `application/msgpack`
+///
+/// Requires the `msgpack` feature
+#[cfg(feature = "msgpack")]
+pub struct MsgPack<T>(PhantomData<T>);
+
+#[cfg(feature = "msgpack")]
+impl<T> MsgPack<T> {
+    /// Create a new [MsgPack] marker
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl<T> Default for MsgPack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Marker to [serialize](crate::serializer::BodySerializer) and [deserialize](crate::deserializer::BodyDeserializer) bodies as [CBOR](https://cbor.io), with `application/cbor`
+///
+/// Requires the `cbor` feature
+#[cfg(feature = "cbor")]
+pub struct Cbor<T>(PhantomData<T>);
+
+#[cfg(feature = "cbor")]
+impl<T> Cbor<T> {
+    /// Create a new [Cbor] marker
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl<T> Default for Cbor<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Marker to [serialize](crate::serializer::BodySerializer) and [deserialize](crate::deserializer::BodyDeserializer) bodies as YAML, with `application/yaml`
+///
+/// Requires the `yaml` feature
+#[cfg(feature = "yaml")]
+pub struct Yaml<T>(PhantomData<T>);
+
+#[cfg(feature = "yaml")]
+impl<T> Yaml<T> {
+    /// Create a new [Yaml] marker
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl<T> Default for Yaml<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Marker to [serialize](crate::serializer::BodySerializer) and [deserialize](crate::deserializer::BodyDeserializer) bodies as TOML, with `application/toml`
+///
+/// Requires the `toml` feature
+#[cfg(feature = "toml")]
+pub struct Toml<T>(PhantomData<T>);
+
+#[cfg(feature = "toml")]
+impl<T> Toml<T> {
+    /// Create a new [Toml] marker
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl<T> Default for Toml<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Marker to [serialize](crate::serializer::BodySerializer) bodies with the serializer preferred
+/// by the `Accept` header of the request
+///
+/// The serializers are listed as a tuple, in order of preference, and all of them must serialize
+/// the same type. When the `Accept` header has the same quality for more than one of them, or
+/// there's no `Accept` header, the first one wins. When none is acceptable, the request is
+/// answered with a `406 Not Acceptable`:
+///
+/// ```rust
+/// # use serde::Serialize;
+/// # use yahf::handler::{Form, Json, Negotiate};
+/// # use yahf::router::Router;
+/// #[derive(Serialize)]
+/// struct User { name: String }
+///
+/// async fn user() -> User
+/// # { User { name: "yahf".into() } }
+///
+/// let router = Router::new().get(
+///     "/user",
+///     user,
+///     &(),
+///     &Negotiate::<(Json<User>, Form<User>)>::new(),
+/// );
+/// ```
+///
+/// Every response, including the `406 Not Acceptable`, is sent with a `Vary: Accept`, so caches
+/// keep one copy per format. Serializers without a
+/// [media type](crate::serializer::BodySerializer::media_type) are never chosen
+pub struct Negotiate<T>(PhantomData<T>);
+
+impl<T> Negotiate<T> {
+    /// Create a new [Negotiate] marker
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Default for Negotiate<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Extract the values of the `{param}` segments from the matched route
+///
+/// The segments are deserialized into `T` using [serde](serde). A single parameter can be
+/// extracted directly into a primitive, while multiple parameters can be extracted into a tuple,
+/// following the declaration order, or into a struct, using the parameter names.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # use yahf::handler::Path;
+/// # use yahf::router::Router;
+/// #[derive(Deserialize)]
+/// struct PostPath {
+///     user_id: u64,
+///     post_id: u64,
+/// }
+///
+/// async fn get_user(Path(id): Path<u64>) -> String
+/// # { id.to_string() }
+/// async fn get_post(path: Path<PostPath>) -> String
+/// # { path.post_id.to_string() }
+///
+/// let router = Router::new()
+///     .get("/users/{id}", get_user, &(), &String::with_capacity(0))
+///     .get("/users/{user_id}/posts/{post_id}", get_post, &(), &String::with_capacity(0));
+/// ```
+///
+/// When the values can't be converted into `T`, the request is answered with a `400 Bad Request`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Path<T>(pub T);
+
+impl<T> Path<T> {
+    /// Consume the [Path] and return the extracted value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Path<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Path<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Extract the query string of the request
+///
+/// The query is deserialized into `T` using [serde](serde), so any missing field that isn't an
+/// [Option] is treated as an error
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # use yahf::handler::Query;
+/// # use yahf::router::Router;
+/// #[derive(Deserialize)]
+/// struct Pagination {
+///     page: u32,
+///     sort: Option<String>,
+/// }
+///
+/// // Answers requests like `/users?page=2&sort=desc`
+/// async fn list_users(query: Query<Pagination>) -> String
+/// # { query.page.to_string() }
+///
+/// let router = Router::new().get("/users", list_users, &(), &String::with_capacity(0));
+/// ```
+///
+/// When the query can't be converted into `T`, the request is answered with a `400 Bad Request`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Query<T>(pub T);
+
+impl<T> Query<T> {
+    /// Consume the [Query] and return the extracted value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Query<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Query<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Extract a state attached with [Router::with_state](crate::router::Router::with_state) or
+/// [Server::with_state](crate::server::Server::with_state)
+///
+/// The state is cloned for every request, so anything expensive to clone should be wrapped in an
+/// [Arc](std::sync::Arc)
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # use yahf::handler::State;
+/// # use yahf::server::Server;
+/// #[derive(Clone)]
+/// struct AppState {
+///     greeting: Arc<String>,
+/// }
+///
+/// async fn greet(State(state): State<AppState>) -> String
+/// # { state.greeting.to_string() }
+///
+/// let server = Server::new()
+///     .with_state(AppState { greeting: Arc::new("Hello".into()) })
+///     .get("/", greet, &(), &String::with_capacity(0));
+/// ```
+///
+/// The [Server](crate::server::Server) panics when it starts if a handler takes a state that was
+/// never attached to its route. A [`Result<State<S>>`](crate::result::Result) makes the state
+/// optional instead
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct State<S>(pub S);
+
+impl<S> State<S> {
+    /// Consume the [State] and return the inner value
+    pub fn into_inner(self) -> S {
+        self.0
+    }
+}
+
+impl<S> State<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    /// Get the state attached to a [Request]
+    ///
+    /// Useful on [middlewares](crate::middleware), that receive the whole [Request]:
+    ///
+    /// ```rust
+    /// # use yahf::body::Body;
+    /// # use yahf::handler::State;
+    /// # use yahf::request::Request;
+    /// # use yahf::result::Result;
+    /// # #[derive(Clone)]
+    /// # struct AppState { enabled: bool }
+    /// async fn middleware(req: Result<Request<Body>>) -> Result<Request<Body>> {
+    ///     if let Ok(req) = req.as_ref() {
+    ///         let enabled = State::<AppState>::from_request(req).is_some_and(|state| state.enabled);
+    ///         // ...
+    /// #       let _ = enabled;
+    ///     }
+    /// #   req
+    /// }
+    /// ```
+    pub fn from_request<T>(req: &Request<T>) -> Option<Self> {
+        req.extensions()
+            .get::<Self>()
+            .cloned()
+    }
+}
+
+impl<S> Deref for State<S> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S> DerefMut for State<S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Object safe version of a [Runner], used to store handlers of different types together
 pub trait BoxedRunner: DynClone + Sync + Send {
-    fn call(
-        &self,
-        req: InternalResult<GenericRequest>,
-    ) -> Pin<Box<dyn Future<Output = InternalResult<GenericResponse>> + Send>>;
+    /// Run the handler with the [Request], returning the generated [Response]
+    fn call(&self, req: InternalResult<GenericRequest>) -> BoxedHandlerFuture;
 }
 
 impl<F> BoxedRunner for F
 where
-    F: Fn(
-            InternalResult<Request<String>>,
-        ) -> Pin<Box<dyn Future<Output = InternalResult<Response<String>>> + Send>>
-        + Sync
-        + DynClone
-        + Send,
+    F: Fn(InternalResult<GenericRequest>) -> BoxedHandlerFuture + Sync + DynClone + Send,
 {
-    fn call(
-        &self,
-        req: InternalResult<GenericRequest>,
-    ) -> Pin<Box<dyn Future<Output = InternalResult<GenericResponse>> + Send>> {
+    fn call(&self, req: InternalResult<GenericRequest>) -> BoxedHandlerFuture {
         self(req)
     }
 }
 
+/// Helper to clone a [BoxedRunner] behind a [Box]
 pub trait DynClone {
+    /// Clone the value into a new [BoxedRunner]
     fn clone_box(&self) -> Box<dyn BoxedRunner>;
 }
 
 impl<F> DynClone for F
 where
-    F: Fn(
-            InternalResult<Request<String>>,
-        ) -> Pin<Box<dyn Future<Output = InternalResult<Response<String>>> + Send>>
-        + Sync
-        + Clone
-        + Send
-        + 'static,
+    F: Fn(InternalResult<GenericRequest>) -> BoxedHandlerFuture + Sync + Clone + Send + 'static,
 {
     fn clone_box(&self) -> Box<dyn BoxedRunner> {
         Box::new(self.clone())
@@ -139,12 +583,12 @@ impl Clone for Box<dyn BoxedRunner> {
     }
 }
 
-impl Runner<(Request<String>, String), (Response<String>, String)> for Box<dyn BoxedRunner> {
+impl Runner<(GenericRequest, ()), (GenericResponse, ())> for Box<dyn BoxedRunner> {
     #[allow(clippy::manual_async_fn)]
     fn call_runner(
         &'_ self,
         run: InternalResult<Request<StandardBodyType>>,
-    ) -> impl Future<Output = InternalResult<Response<String>>> + Send + '_ {
+    ) -> impl Future<Output = InternalResult<Response<StandardBodyType>>> + Send + '_ {
         async move { self.call(run).await }
     }
 }
@@ -153,11 +597,7 @@ pub(crate) fn encapsulate_runner<FnInput, FnOutput, Deserializer, Serializer, R>
     runner: R,
     _deserializer: &Deserializer,
     _serializer: &Serializer,
-) -> impl Fn(
-    InternalResult<Request<String>>,
-) -> Pin<Box<dyn Future<Output = InternalResult<Response<String>>> + Send>>
-       + Sync
-       + DynClone
+) -> impl Fn(InternalResult<GenericRequest>) -> BoxedHandlerFuture + Sync + DynClone
 where
     R: Runner<(FnInput, Deserializer), (FnOutput, Serializer)> + 'static,
     Deserializer: 'static,
@@ -170,8 +610,8 @@ where
 
 async fn call_runner<FnInput, FnOutput, Deserializer, Serializer, R>(
     runner: R,
-    req: InternalResult<Request<String>>,
-) -> InternalResult<Response<String>>
+    req: InternalResult<GenericRequest>,
+) -> InternalResult<GenericResponse>
 where
     R: Runner<(FnInput, Deserializer), (FnOutput, Serializer)>,
 {
@@ -183,10 +623,12 @@ mod tests {
 
     use serde::{Deserialize, Serialize};
 
-    use crate::handler::Json;
+    use crate::handler::{Json, Path, Query};
+    use crate::path::PathParams;
+    use crate::request::{HeaderMap, Method};
     use crate::result::Result;
 
-    use super::{encapsulate_runner, Request, Response};
+    use super::{encapsulate_runner, Body, Request, Response};
 
     #[derive(Deserialize, Serialize)]
     struct SomeBodyType {
@@ -239,12 +681,18 @@ mod tests {
     async fn test_simple_handler_implements_runner() -> std::io::Result<()> {
         let a = encapsulate_runner(simple_handler, &Json::new(), &Json::new());
         let c = Request::builder()
+            .header("Content-Type", "application/json")
             .body(serde_json::json!({ "field": "South of the border" }).to_string());
         let b = a(c.into()).await;
 
         assert_eq!(
-            b.unwrap().body().as_str(),
-            serde_json::json!({ "field": "South of the border - Ed Sheeran"  }).to_string()
+            b.unwrap()
+                .body()
+                .as_bytes()
+                .unwrap(),
+            serde_json::json!({ "field": "South of the border - Ed Sheeran"  })
+                .to_string()
+                .as_bytes()
         );
 
         Ok(())
@@ -260,8 +708,13 @@ mod tests {
         let expected_field_result = "HOPE - NF";
 
         assert_eq!(
-            b.unwrap().body().as_str(),
-            serde_json::json!({ "field": expected_field_result }).to_string()
+            b.unwrap()
+                .body()
+                .as_bytes()
+                .unwrap(),
+            serde_json::json!({ "field": expected_field_result })
+                .to_string()
+                .as_bytes()
         );
 
         Ok(())
@@ -277,8 +730,13 @@ mod tests {
         let expected_field_result = "HOPE - NF";
 
         assert_eq!(
-            b.unwrap().body().as_str(),
-            serde_json::json!({ "field": expected_field_result }).to_string()
+            b.unwrap()
+                .body()
+                .as_bytes()
+                .unwrap(),
+            serde_json::json!({ "field": expected_field_result })
+                .to_string()
+                .as_bytes()
         );
 
         Ok(())
@@ -287,14 +745,21 @@ mod tests {
     #[tokio::test]
     async fn test_simple_handler_with_body_implements_runner() -> std::io::Result<()> {
         let a = encapsulate_runner(simple_handler_with_body, &Json::new(), &Json::new());
-        let c = Request::builder().body(serde_json::json!({ "field": "So Good" }).to_string());
+        let c = Request::builder()
+            .header("Content-Type", "application/json")
+            .body(serde_json::json!({ "field": "So Good" }).to_string());
         let b = a(c.into()).await;
 
         let expected_field_result = "So Good - Halsey";
 
         assert_eq!(
-            b.unwrap().body().as_str(),
-            serde_json::json!({ "field": expected_field_result }).to_string()
+            b.unwrap()
+                .body()
+                .as_bytes()
+                .unwrap(),
+            serde_json::json!({ "field": expected_field_result })
+                .to_string()
+                .as_bytes()
         );
 
         Ok(())
@@ -307,14 +772,21 @@ mod tests {
             &Json::new(),
             &Json::new(),
         );
-        let c = Request::builder().body(serde_json::json!({ "field": "Sharks" }).to_string());
+        let c = Request::builder()
+            .header("Content-Type", "application/json")
+            .body(serde_json::json!({ "field": "Sharks" }).to_string());
         let b = a(c.into()).await;
 
         let expected_field_result = "Sharks - Imagine Dragons";
 
         assert_eq!(
-            b.unwrap().body().as_str(),
-            serde_json::json!({ "field": expected_field_result }).to_string()
+            b.unwrap()
+                .body()
+                .as_bytes()
+                .unwrap(),
+            serde_json::json!({ "field": expected_field_result })
+                .to_string()
+                .as_bytes()
         );
 
         Ok(())
@@ -327,14 +799,164 @@ mod tests {
             &Json::new(),
             &Json::new(),
         );
-        let c = Request::builder().body(serde_json::json!({ "field": "Venom" }).to_string());
+        let c = Request::builder()
+            .header("Content-Type", "application/json")
+            .body(serde_json::json!({ "field": "Venom" }).to_string());
         let b = a(c.into()).await;
 
         let expected_field_result = "Venom - Eminem";
 
         assert_eq!(
-            b.unwrap().body().as_str(),
-            serde_json::json!({ "field": expected_field_result }).to_string()
+            b.unwrap()
+                .body()
+                .as_bytes()
+                .unwrap(),
+            serde_json::json!({ "field": expected_field_result })
+                .to_string()
+                .as_bytes()
+        );
+
+        Ok(())
+    }
+
+    #[derive(Deserialize)]
+    struct Filter {
+        artist: String,
+    }
+
+    async fn handler_with_multiple_extractors(
+        Path(id): Path<u32>,
+        Query(filter): Query<Filter>,
+        input: SomeBodyType,
+    ) -> SomeBodyType {
+        SomeBodyType {
+            field: format!("{} - {} - {}", id, input.field, filter.artist),
+        }
+    }
+
+    async fn handler_with_method_and_headers(
+        method: Method,
+        headers: HeaderMap,
+        input: String,
+    ) -> String {
+        format!(
+            "{} - {} - {}",
+            method,
+            headers
+                .get("x-artist")
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default(),
+            input
+        )
+    }
+
+    async fn handler_with_fallible_extractor(
+        path: Result<Path<u32>>,
+        Query(filter): Query<Filter>,
+    ) -> String {
+        match path.into_inner() {
+            Ok(Path(id)) => format!("{} - {}", id, filter.artist),
+            Err(err) => format!("{} - {}", err.code(), filter.artist),
+        }
+    }
+
+    fn request_with_path_params(
+        uri: &str,
+        params: Vec<(String, String)>,
+        body: String,
+    ) -> Request<Body> {
+        let mut request = Request::builder()
+            .header("Content-Type", "application/json")
+            .uri(uri)
+            .body(body);
+        request
+            .extensions_mut()
+            .insert(PathParams::new(params));
+        request
+    }
+
+    #[tokio::test]
+    async fn test_handler_with_multiple_extractors_implements_runner() -> std::io::Result<()> {
+        let a = encapsulate_runner(handler_with_multiple_extractors, &Json::new(), &Json::new());
+        let c = request_with_path_params(
+            "/songs/1?artist=Queen",
+            vec![("id".into(), "1".into())],
+            serde_json::json!({ "field": "Bohemian Rhapsody" }).to_string(),
+        );
+        let b = a(c.into()).await;
+
+        assert_eq!(
+            b.unwrap()
+                .body()
+                .as_bytes()
+                .unwrap(),
+            serde_json::json!({ "field": "1 - Bohemian Rhapsody - Queen" })
+                .to_string()
+                .as_bytes()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handler_with_method_and_headers_implements_runner() -> std::io::Result<()> {
+        let a = encapsulate_runner(
+            handler_with_method_and_headers,
+            &String::with_capacity(0),
+            &String::with_capacity(0),
+        );
+        let c = Request::builder()
+            .method(Method::POST)
+            .header("x-artist", "Muse")
+            .body("Uprising".to_owned());
+        let b = a(c.into()).await;
+
+        assert_eq!(
+            b.unwrap()
+                .body()
+                .as_bytes()
+                .unwrap(),
+            "POST - Muse - Uprising"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handler_with_failing_extractor() -> std::io::Result<()> {
+        let a = encapsulate_runner(handler_with_multiple_extractors, &Json::new(), &Json::new());
+        let c = request_with_path_params(
+            "/songs/1",
+            vec![("id".into(), "1".into())],
+            serde_json::json!({ "field": "Bohemian Rhapsody" }).to_string(),
+        );
+        let b = a(c.into()).await;
+
+        assert_eq!(b.unwrap_err().code(), &400);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handler_with_fallible_extractor() -> std::io::Result<()> {
+        let a = encapsulate_runner(
+            handler_with_fallible_extractor,
+            &(),
+            &String::with_capacity(0),
+        );
+        let c = request_with_path_params(
+            "/songs/one?artist=Queen",
+            vec![("id".into(), "one".into())],
+            String::with_capacity(0),
+        );
+        let b = a(c.into()).await;
+
+        assert_eq!(
+            b.unwrap()
+                .body()
+                .as_bytes()
+                .unwrap(),
+            "400 - Queen"
         );
 
         Ok(())
@@ -123,6 +123,44 @@
 //!
 //! All these signatures comes from the implementations of [`RunnerInput`](runner_input::RunnerInput) and [`RunnerOutput`](runner_output::RunnerOutput).
 //!
+//! A `handler` can also receive up to 12 arguments. Every argument, except the last one, is
+//! extracted from the request head, like [`Path`](handler::Path) and [`Query`](handler::Query),
+//! while the last one is the only one that can consume the body:
+//!
+//! ```no_run
+//! # use serde::Deserialize;
+//! use yahf::handler::{Path, Query};
+//! # #[derive(Deserialize)]
+//! # struct Filter { active: bool }
+//! # #[derive(Deserialize)]
+//! # struct RequestBody { first_value: u64 }
+//!
+//! async fn handler13(path: Path<u64>, query: Query<Filter>, body: RequestBody) -> String
+//! # {todo!()}
+//! ```
+//!
+//! Bodies aren't buffered unless the deserializer needs them to be, so a `handler` can also
+//! receive and return a [`Body`](body::Body) to stream large or binary payloads. Uploads sent as
+//! `multipart/form-data` are read field by field with a [`Multipart`](multipart::Multipart).
+//! Bodies larger than the [`Limits`](limits::Limits) of the server, or the
+//! [`BodyLimit`](limits::BodyLimit) of the route, are answered with a `413 Payload Too Large`.
+//!
+//! **Breaking change:** request bodies used to be unlimited, and are now limited to
+//! [`DEFAULT_BODY_LIMIT`](limits::DEFAULT_BODY_LIMIT), 8 MiB, by default. That includes the
+//! streamed ones, read with a [`Body`](body::Body) or a [`Multipart`](multipart::Multipart), so
+//! routes taking larger uploads must raise their [`BodyLimit`](limits::BodyLimit), or the server
+//! must remove the limit with [`Limits::unlimited_body`](limits::Limits::unlimited_body).
+//!
+//! Besides [`Json`](handler::Json) and [`Form`](handler::Form), bodies can be serialized and
+//! deserialized as MessagePack, CBOR, YAML and TOML, with the markers enabled by the `msgpack`,
+//! `cbor`, `yaml` and `toml` features. A route can offer more than one format with
+//! [`Negotiate`](handler::Negotiate), that picks the serializer preferred by the `Accept` header.
+//!
+//! A `handler` can also return `(StatusCode, T)`, `(StatusCode, HeaderMap, T)`, a
+//! [`StatusCode`](response::StatusCode), a [`Redirect`](response::Redirect), or any type
+//! implementing [`IntoResponse`](response::IntoResponse), to set the status and headers of the
+//! response without building it by hand.
+//!
 //! # Extensability
 //!
 //! YAHF `handlers` are modular by design. A `handler` is decomposed into four modules: a body [`deserializer`](deserializer::BodyDeserializer),
@@ -140,6 +178,7 @@
 //! ```rust
 //! use serde::Deserialize;
 //! use serde::Serialize;
+//! use yahf::body::Body;
 //! use yahf::handler::Json;
 //! use yahf::request::Request;
 //! use yahf::result::Result;
@@ -156,7 +195,7 @@
 //!# }
 //!
 //! // Print the time, the method, and the path from the Request
-//! async fn log_middleware(req: Result<Request<String>>) -> Result<Request<String>>
+//! async fn log_middleware(req: Result<Request<Body>>) -> Result<Request<Body>>
 //!# {
 //!#     match req.into_inner() {
 //!#        Ok(req) => {
@@ -177,7 +216,7 @@
 //!# }
 //!
 //! // Handle any possible errors
-//! async fn log_error(res: Result<Response<String>>) -> Result<Response<String>>
+//! async fn log_error(res: Result<Response<Body>>) -> Result<Response<Body>>
 //!# {
 //!#    match res.into_inner() {
 //!#        Err(err) => {
@@ -217,17 +256,39 @@
 //!
 //! More of this example [here](https://github.com/lucasduartesobreira/yahf/blob/main/examples/router_example/main.rs)
 //!
+//! To see both the request and the response in the same call, like to time a request or to
+//! retry the handler, an [`AroundMiddleware`](middleware::AroundMiddleware) receives the
+//! [`Next`](middleware::Next) step of the chain and is appended with
+//! [`around`](router::Router::around). The built-in [`Timeout`](middleware::Timeout) is one, that
+//! answers slow handlers with a `503 Service Unavailable`.
+//!
+//! A middleware can also be scoped to a single path, by binding a [`Route`](router::Route) with
+//! [`route`](router::Router::route) and appending the middleware with
+//! [`with_pre`](router::Route::with_pre), [`with_after`](router::Route::with_after) or
+//! [`with_around`](router::Route::with_around).
+//!
+//! Middlewares that own their state, like a rate limiter or a connection pool, implement the
+//! [`Middleware`](middleware::Middleware) trait and are wrapped by a
+//! [`Stateful`](middleware::Stateful), which can be passed to `pre` and `after` as any other
+//! middleware.
+//!
 //! # Examples
 //!
 //! The repo includes [illustrative examples](https://github.com/lucasduartesobreira/yahf/tree/main/examples) demonstrating the integration of all the components
 //!
 
+mod accept;
+pub mod body;
+mod connection;
 #[doc(hidden)]
 pub mod deserializer;
-#[doc(hidden)]
 pub mod error;
+mod fallback;
 pub mod handler;
+pub mod limits;
 pub mod middleware;
+pub mod multipart;
+mod path;
 pub mod request;
 pub mod response;
 pub mod result;
@@ -239,5 +300,7 @@ pub mod runner_output;
 #[doc(hidden)]
 pub mod serializer;
 pub mod server;
+mod state;
+pub mod testing;
 #[doc(hidden)]
 pub mod tree;
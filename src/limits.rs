@@ -0,0 +1,372 @@
+//! Limits of the requests accepted by a [Server](crate::server::Server)
+//!
+//! Refeer to the [Limits] and the [BodyLimit] for more information
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures::Stream;
+
+use crate::{
+    body::{Body, BoxError},
+    error::Error,
+    request::HeaderMap,
+    result::InternalResult,
+};
+
+/// Default limit of a request body, 8 MiB
+///
+/// Bodies used to be unlimited, so streamed uploads larger than this need a
+/// [BodyLimit] on their route, or [Limits::unlimited_body]
+pub const DEFAULT_BODY_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// Default limit of the number of headers of a request
+pub const DEFAULT_HEADER_COUNT: usize = 100;
+
+/// Default limit of the size of the headers of a request, 16 KiB
+pub const DEFAULT_HEADER_SIZE: usize = 16 * 1024;
+
+/// Room left in an HTTP/1 request head for the request line and the separators of the headers,
+/// also the smallest buffer hyper accepts
+const HEAD_OVERHEAD: usize = 8 * 1024;
+
+/// Limits of every request accepted by a [Server](crate::server::Server)
+///
+/// ```rust
+/// use yahf::limits::Limits;
+/// use yahf::server::Server;
+///
+/// let server = Server::new().limits(
+///     Limits::new()
+///         .body(1024 * 1024)
+///         .header_count(50)
+///         .header_size(8 * 1024),
+/// );
+/// ```
+///
+/// A body larger than the limit is answered with a `413 Payload Too Large`. When its
+/// `Content-Length` is too large, that happens before reaching any middleware or handler, even the
+/// ones that never read the body. Otherwise, it happens once too many bytes were read. Too many or too
+/// large headers are answered with a `431 Request Header Fields Too Large`, before reaching any
+/// middleware.
+///
+/// The body limit can be changed for some routes with a [BodyLimit]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    body: Option<u64>,
+    header_count: Option<usize>,
+    header_size: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            body: Some(DEFAULT_BODY_LIMIT),
+            header_count: Some(DEFAULT_HEADER_COUNT),
+            header_size: Some(DEFAULT_HEADER_SIZE),
+        }
+    }
+}
+
+impl Limits {
+    /// Create the default [Limits]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the size of the body, in bytes
+    pub fn body(mut self, limit: u64) -> Self {
+        self.body = Some(limit);
+        self
+    }
+
+    /// Remove the limit of the body
+    pub fn unlimited_body(mut self) -> Self {
+        self.body = None;
+        self
+    }
+
+    /// Limit the number of headers
+    pub fn header_count(mut self, limit: usize) -> Self {
+        self.header_count = Some(limit);
+        self
+    }
+
+    /// Limit the size of the headers, counting the bytes of every name and value
+    pub fn header_size(mut self, limit: usize) -> Self {
+        self.header_size = Some(limit);
+        self
+    }
+
+    /// Remove the limits of the headers
+    pub fn unlimited_headers(mut self) -> Self {
+        self.header_count = None;
+        self.header_size = None;
+        self
+    }
+
+    /// Largest HTTP/1 request head hyper buffers while parsing, so oversized headers are refused
+    /// before they are read whole
+    pub(crate) fn http1_max_buf_size(&self) -> Option<usize> {
+        self.header_size
+            .map(|size| size.saturating_add(HEAD_OVERHEAD))
+    }
+
+    /// Largest HTTP/2 header list hyper accepts, counting 32 bytes more for each header
+    pub(crate) fn http2_max_header_list_size(&self) -> Option<u32> {
+        self.header_size.map(|size| {
+            let overhead = 32
+                * self
+                    .header_count
+                    .unwrap_or(DEFAULT_HEADER_COUNT);
+
+            u32::try_from(size.saturating_add(overhead)).unwrap_or(u32::MAX)
+        })
+    }
+
+    pub(crate) fn check_headers(&self, headers: &HeaderMap) -> InternalResult<()> {
+        if self
+            .header_count
+            .is_some_and(|limit| headers.len() > limit)
+        {
+            return Err(headers_too_large("Too many headers"));
+        }
+
+        let size: usize = headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+        if self
+            .header_size
+            .is_some_and(|limit| size > limit)
+        {
+            return Err(headers_too_large("Headers too large"));
+        }
+
+        Ok(())
+    }
+
+    /// Wrap the `body` so it can't be read past the body limit, or the `route` one when there's
+    /// one
+    ///
+    /// Fails right away when the `Content-Length` is larger than the limit
+    pub(crate) fn limit_body(
+        &self,
+        headers: &HeaderMap,
+        body: Body,
+        route: Option<BodyLimit>,
+    ) -> InternalResult<Body> {
+        let limit = match route {
+            Some(BodyLimit(limit)) => limit,
+            None => self.body,
+        };
+        let Some(limit) = limit else {
+            return Ok(body);
+        };
+
+        if content_length(headers).is_some_and(|length| length > limit) {
+            return Err(payload_too_large(limit));
+        }
+
+        Ok(Body::from_stream(Limited {
+            inner: body,
+            limit,
+            read: 0,
+            exceeded: false,
+        }))
+    }
+}
+
+/// Body limit of a [Route](crate::router::Route), that replaces the one set by the [Limits] of the
+/// [Server](crate::server::Server)
+///
+/// It's usually set to accept larger uploads or to be stricter than the rest of the
+/// [Server](crate::server::Server):
+///
+/// ```rust
+/// use yahf::body::Body;
+/// use yahf::limits::BodyLimit;
+/// use yahf::router::Route;
+/// use yahf::server::Server;
+///
+/// async fn upload(body: Body) -> String
+/// # { String::new() }
+///
+/// let server = Server::new().route(
+///     "/upload",
+///     Route::new()
+///         .post(upload, &(), &String::with_capacity(0))
+///         .body_limit(BodyLimit::new(64 * 1024 * 1024)),
+/// );
+/// ```
+///
+/// Since the limit is known as soon as the route is found, a `Content-Length` larger than it is
+/// answered with a `413 Payload Too Large` before running any middleware, including the ones of
+/// the [Server](crate::server::Server)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimit(Option<u64>);
+
+impl BodyLimit {
+    /// Limit the size of the body, in bytes
+    pub fn new(limit: u64) -> Self {
+        Self(Some(limit))
+    }
+
+    /// Remove the limit of the body
+    pub fn unlimited() -> Self {
+        Self(None)
+    }
+}
+
+struct Limited {
+    inner: Body,
+    limit: u64,
+    read: u64,
+    exceeded: bool,
+}
+
+impl Stream for Limited {
+    type Item = std::result::Result<Bytes, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.exceeded {
+            return Poll::Ready(None);
+        }
+
+        let limit = self.limit;
+        match Pin::new(&mut self.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                self.read += chunk.len() as u64;
+                if self.read > limit {
+                    self.exceeded = true;
+                    Poll::Ready(Some(Err(payload_too_large(limit).into())))
+                } else {
+                    Poll::Ready(Some(Ok(chunk)))
+                }
+            }
+            other => other,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.exceeded {
+            true => (0, Some(0)),
+            false => (0, self.inner.size_hint().1),
+        }
+    }
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(http::header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse().ok())
+}
+
+fn payload_too_large(limit: u64) -> Error {
+    Error::new(format!("Body larger than {} bytes", limit), 413)
+        .with_error_code("payload_too_large")
+}
+
+fn headers_too_large(message: &str) -> Error {
+    Error::new(message.into(), 431).with_error_code("request_header_fields_too_large")
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use crate::{body::Body, request::HeaderMap};
+
+    use super::{BodyLimit, Limits};
+
+    fn chunked(chunks: &'static [&'static str]) -> Body {
+        Body::from_stream(futures::stream::iter(
+            chunks
+                .iter()
+                .map(|chunk| Ok::<_, std::io::Error>(*chunk)),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_body_limit_while_streaming() {
+        let limits = Limits::new().body(8);
+
+        let body = limits
+            .limit_body(&HeaderMap::new(), chunked(&["1234", "5678"]), None)
+            .unwrap();
+        assert_eq!(body.bytes().await.unwrap(), "12345678");
+
+        let body = limits
+            .limit_body(&HeaderMap::new(), chunked(&["1234", "56789"]), None)
+            .unwrap();
+        assert_eq!(
+            body.bytes()
+                .await
+                .err()
+                .map(|err| *err.code()),
+            Some(413)
+        );
+    }
+
+    #[test]
+    fn test_body_limit_with_content_length() {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::CONTENT_LENGTH, HeaderValue::from(64));
+
+        let limits = Limits::new().body(8);
+
+        assert_eq!(
+            limits
+                .limit_body(&headers, chunked(&["1234"]), None)
+                .err()
+                .map(|err| *err.code()),
+            Some(413)
+        );
+        assert_eq!(
+            limits
+                .limit_body(&headers, chunked(&["1234"]), Some(BodyLimit::new(32)))
+                .err()
+                .map(|err| *err.code()),
+            Some(413)
+        );
+
+        // The BodyLimit of the route replaces the one of the Limits
+        assert!(limits
+            .limit_body(&headers, chunked(&["1234"]), Some(BodyLimit::new(64)))
+            .is_ok());
+        assert!(limits
+            .limit_body(&headers, chunked(&["1234"]), Some(BodyLimit::unlimited()))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_header_limits() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-first", HeaderValue::from_static("1"));
+        headers.insert("x-second", HeaderValue::from_static("2"));
+
+        assert!(Limits::new()
+            .check_headers(&headers)
+            .is_ok());
+        assert_eq!(
+            Limits::new()
+                .header_count(1)
+                .check_headers(&headers)
+                .err()
+                .map(|err| *err.code()),
+            Some(431)
+        );
+        assert_eq!(
+            Limits::new()
+                .header_size(8)
+                .check_headers(&headers)
+                .err()
+                .map(|err| *err.code()),
+            Some(431)
+        );
+    }
+}
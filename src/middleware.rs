@@ -1,82 +1,436 @@
-//! Async functions that runs before or after the handler
-
-use std::sync::Arc;
+//! Async functions that runs before, after or around the handler
+
+use std::{
+    fmt::Debug,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use futures::Future;
+use tokio::sync::OnceCell;
 
 use crate::{
-    handler::Runner,
+    body::Body,
+    error::Error,
+    handler::{BoxedHandlerFuture, Extractors, Returns, Runner},
     request::Request,
     response::Response,
     result::{InternalResult, Result},
 };
 
-pub trait PreMiddleware: Send + Sync + Copy {
+/// Describes an async function that runs before the handler
+///
+/// It receives the [Request] or the error produced by a previous [PreMiddleware], and can either
+/// transform the request or short-circuit with an error
+pub trait PreMiddleware: Send + Sync + Clone {
+    /// [Future] returned by the middleware
     type FutCallResponse;
-    fn call(&self, error: InternalResult<Request<String>>) -> Self::FutCallResponse;
+    /// Run the middleware
+    fn call(&self, error: InternalResult<Request<Body>>) -> Self::FutCallResponse;
 }
 
 impl<MidFn, Fut, CF> PreMiddleware for MidFn
 where
-    MidFn: Fn(Result<Request<String>>) -> Fut + Send + Sync + Copy,
+    MidFn: Fn(Result<Request<Body>>) -> Fut + Send + Sync + Clone,
     Fut: Future<Output = CF>,
-    CF: Into<InternalResult<Request<String>>>,
+    CF: Into<InternalResult<Request<Body>>>,
 {
     type FutCallResponse = Fut;
 
     #[inline(always)]
-    fn call(&self, error: InternalResult<Request<String>>) -> Self::FutCallResponse {
+    fn call(&self, error: InternalResult<Request<Body>>) -> Self::FutCallResponse {
         self(error.into())
     }
 }
 
-pub trait AfterMiddleware: Send + Sync + Copy {
+/// Describes an async function that runs after the handler
+///
+/// It receives the [Response] or the error produced by the handler or by a previous
+/// [AfterMiddleware], and can either transform the response or handle the error
+pub trait AfterMiddleware: Send + Sync + Clone {
+    /// [Future] returned by the middleware
     type FutCallResponse;
-    fn call(&self, error: InternalResult<Response<String>>) -> Self::FutCallResponse;
+    /// Run the middleware
+    fn call(&self, error: InternalResult<Response<Body>>) -> Self::FutCallResponse;
 }
 
 impl<MidFn, Fut, CF> AfterMiddleware for MidFn
 where
-    MidFn: Fn(Result<Response<String>>) -> Fut + Send + Sync + Copy,
+    MidFn: Fn(Result<Response<Body>>) -> Fut + Send + Sync + Clone,
     Fut: Future<Output = CF>,
-    CF: Into<InternalResult<Response<String>>>,
+    CF: Into<InternalResult<Response<Body>>>,
 {
     type FutCallResponse = Fut;
 
     #[inline(always)]
-    fn call(&self, error: InternalResult<Response<String>>) -> Self::FutCallResponse {
+    fn call(&self, error: InternalResult<Response<Body>>) -> Self::FutCallResponse {
         self(error.into())
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+/// Describes a middleware that owns its state, like a rate limiter or a metrics registry
+///
+/// Unlike the async functions, any struct can implement [Middleware], and it's used as a
+/// [PreMiddleware] or an [AfterMiddleware] once wrapped by a [Stateful]. Every method has a
+/// default implementation, so only the needed ones must be implemented:
+///
+/// ```rust
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use yahf::body::Body;
+/// use yahf::middleware::{Middleware, Stateful};
+/// use yahf::request::Request;
+/// use yahf::result::Result;
+/// use yahf::router::Router;
+///
+/// #[derive(Default)]
+/// struct Counter {
+///     requests: AtomicUsize,
+/// }
+///
+/// impl Middleware for Counter {
+///     async fn pre(&self, req: Result<Request<Body>>) -> Result<Request<Body>> {
+///         self.requests.fetch_add(1, Ordering::Relaxed);
+///         req
+///     }
+/// }
+///
+/// // Both routers share the same `Counter`
+/// let counter = Stateful::new(Counter::default());
+/// let users = Router::new().pre(counter.clone());
+/// let orders = Router::new().pre(counter);
+/// ```
+pub trait Middleware: Send + Sync + 'static {
+    /// Prepare the middleware, like opening a connection
+    ///
+    /// It runs once, before the first request or response reaches the middleware. When it fails,
+    /// the request or the response gets the error and the setup runs again on the next call
+    fn setup(&self) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()).into() }
+    }
+
+    /// Run before the handler, just like a [PreMiddleware]
+    fn pre(
+        &self,
+        req: Result<Request<Body>>,
+    ) -> impl Future<Output = Result<Request<Body>>> + Send {
+        async { req }
+    }
+
+    /// Run after the handler, just like an [AfterMiddleware]
+    fn after(
+        &self,
+        res: Result<Response<Body>>,
+    ) -> impl Future<Output = Result<Response<Body>>> + Send {
+        async { res }
+    }
+}
+
+/// Shared handle to a [Middleware], accepted as a [PreMiddleware] and as an [AfterMiddleware]
+///
+/// The [Middleware] lives behind an [Arc], so every request and every clone of the [Stateful]
+/// sees the same state
+pub struct Stateful<M>(Arc<StatefulInner<M>>);
+
+struct StatefulInner<M> {
+    middleware: M,
+    ready: OnceCell<()>,
+}
+
+impl<M> Stateful<M>
+where
+    M: Middleware,
+{
+    /// Wrap the [Middleware]
+    pub fn new(middleware: M) -> Self {
+        Self(Arc::new(StatefulInner {
+            middleware,
+            ready: OnceCell::new(),
+        }))
+    }
+
+    async fn setup(&self) -> InternalResult<()> {
+        self.0
+            .ready
+            .get_or_try_init(|| async {
+                self.0
+                    .middleware
+                    .setup()
+                    .await
+                    .into_inner()
+            })
+            .await
+            .map(|_| ())
+    }
+}
+
+impl<M> Clone for Stateful<M> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<M> PreMiddleware for Stateful<M>
+where
+    M: Middleware,
+{
+    type FutCallResponse = Pin<Box<dyn Future<Output = Result<Request<Body>>> + Send>>;
+
+    fn call(&self, req: InternalResult<Request<Body>>) -> Self::FutCallResponse {
+        let stateful = self.clone();
+        Box::pin(async move {
+            match stateful.setup().await {
+                Ok(()) => {
+                    stateful
+                        .0
+                        .middleware
+                        .pre(req.into())
+                        .await
+                }
+                Err(err) => Err(err).into(),
+            }
+        })
+    }
+}
+
+impl<M> AfterMiddleware for Stateful<M>
+where
+    M: Middleware,
+{
+    type FutCallResponse = Pin<Box<dyn Future<Output = Result<Response<Body>>> + Send>>;
+
+    fn call(&self, res: InternalResult<Response<Body>>) -> Self::FutCallResponse {
+        let stateful = self.clone();
+        Box::pin(async move {
+            match stateful.setup().await {
+                Ok(()) => {
+                    stateful
+                        .0
+                        .middleware
+                        .after(res.into())
+                        .await
+                }
+                Err(err) => Err(err).into(),
+            }
+        })
+    }
+}
+
+/// Describes an async function that runs around the handler
+///
+/// It receives the [Request], or the error produced by the [PreMiddlewares](PreMiddleware), and
+/// the [Next] step of the chain, that runs the handler. Since the same call sees both the request
+/// and the response, it can keep state across them, like timing the request, or even run the
+/// handler more than once:
+///
+/// ```rust
+/// use std::time::Instant;
+/// use yahf::body::Body;
+/// use yahf::middleware::Next;
+/// use yahf::request::Request;
+/// use yahf::response::Response;
+/// use yahf::result::Result;
+///
+/// async fn timing(req: Result<Request<Body>>, next: Next) -> Result<Response<Body>> {
+///     let start = Instant::now();
+///     let res = next.run(req).await;
+///     println!("Took {:?}", start.elapsed());
+///
+///     res
+/// }
+/// # let router = yahf::router::Router::new().around(timing);
+/// ```
+///
+/// Unlike [PreMiddleware] and [AfterMiddleware], it doesn't need to be [Clone], so it can own its
+/// state
+pub trait AroundMiddleware: Send + Sync + 'static {
+    /// [Future] returned by the middleware
+    type FutCallResponse;
+    /// Run the middleware
+    fn call(&self, req: InternalResult<Request<Body>>, next: Next) -> Self::FutCallResponse;
+}
+
+impl<MidFn, Fut, CF> AroundMiddleware for MidFn
+where
+    MidFn: Fn(Result<Request<Body>>, Next) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = CF>,
+    CF: Into<InternalResult<Response<Body>>>,
+{
+    type FutCallResponse = Fut;
+
+    #[inline(always)]
+    fn call(&self, req: InternalResult<Request<Body>>, next: Next) -> Self::FutCallResponse {
+        self(req.into(), next)
+    }
+}
+
+type BoxedNext = Arc<dyn Fn(InternalResult<Request<Body>>) -> BoxedHandlerFuture + Send + Sync>;
+type BoxedAround =
+    Arc<dyn Fn(InternalResult<Request<Body>>, Next) -> BoxedHandlerFuture + Send + Sync>;
+
+/// Rest of the chain of an [AroundMiddleware], running the next [AroundMiddleware] or the
+/// handler
+#[derive(Clone)]
+pub struct Next(BoxedNext);
+
+impl Next {
+    /// Run the rest of the chain with the [Request], returning its [Response]
+    ///
+    /// [Next] can be run more than once, as long as a new [Request] is given each time
+    pub async fn run(&self, req: Result<Request<Body>>) -> Result<Response<Body>> {
+        (self.0)(req.into_inner())
+            .await
+            .into()
+    }
+}
+
+/// [AroundMiddleware] that bounds how long the rest of the chain, and the handler, can run
+///
+/// Once the duration is over, the handler is dropped and the request is answered with a `503
+/// Service Unavailable`, or a `408 Request Timeout`, that goes through the
+/// [AfterMiddlewares](AfterMiddleware) as any other error. It can be set for every route, and
+/// replaced on the slower ones:
+///
+/// ```rust
+/// use std::time::Duration;
+/// use yahf::middleware::Timeout;
+/// use yahf::router::Route;
+/// use yahf::server::Server;
+///
+/// async fn report() -> String
+/// # { String::new() }
+///
+/// let server = Server::new()
+///     .around(Timeout::new(Duration::from_secs(10)))
+///     .route(
+///         "/report",
+///         Route::new()
+///             .get(report, &(), &String::with_capacity(0))
+///             .with_around(Timeout::new(Duration::from_secs(60)).request_timeout()),
+///     );
+/// ```
+///
+/// A [Timeout] replaces the ones appended before it, even when it's longer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout {
+    duration: Duration,
+    status: u16,
+}
+
+impl Timeout {
+    /// Answer with a `503 Service Unavailable` once the `duration` is over
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            status: 503,
+        }
+    }
+
+    /// Answer with a `408 Request Timeout` instead
+    pub fn request_timeout(mut self) -> Self {
+        self.status = 408;
+        self
+    }
+
+    fn error(&self) -> Error {
+        let error_code = match self.status {
+            408 => "request_timeout",
+            _ => "timeout",
+        };
+
+        Error::new(format!("Timed out after {:?}", self.duration), self.status)
+            .with_error_code(error_code)
+    }
+}
+
+/// Set by an inner [Timeout], so the outer ones let it decide
+#[derive(Clone)]
+struct TimeoutReplaced(Arc<AtomicBool>);
+
+impl AroundMiddleware for Timeout {
+    type FutCallResponse = Pin<Box<dyn Future<Output = Result<Response<Body>>> + Send>>;
+
+    fn call(&self, req: InternalResult<Request<Body>>, next: Next) -> Self::FutCallResponse {
+        let timeout = *self;
+        let replaced = Arc::new(AtomicBool::new(false));
+        let req = req.map(|mut req| {
+            if let Some(outer) = req
+                .extensions_mut()
+                .insert(TimeoutReplaced(replaced.clone()))
+            {
+                outer
+                    .0
+                    .store(true, Ordering::SeqCst);
+            }
+            req
+        });
+
+        Box::pin(async move {
+            let mut res = Box::pin(async move { next.run(req.into()).await });
+
+            tokio::select! {
+                res = &mut res => res,
+                _ = tokio::time::sleep(timeout.duration) => {
+                    if replaced.load(Ordering::SeqCst) {
+                        res.await
+                    } else {
+                        Err(timeout.error()).into()
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// [AroundMiddlewares](AroundMiddleware) of a [MiddlewareFactory], from the outermost to the
+/// innermost
+#[derive(Clone, Default)]
+struct Arounds(Vec<BoxedAround>);
+
+impl Debug for Arounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Arounds")
+            .field(&self.0.len())
+            .finish()
+    }
+}
+
+/// Chain of [PreMiddleware], [AroundMiddleware] and [AfterMiddleware] that can be applied to a
+/// handler
+#[derive(Debug, Default, Clone)]
 pub struct MiddlewareFactory<FPre, FAfter> {
     pre: FPre,
     after: FAfter,
+    arounds: Arounds,
 }
 
 impl MiddlewareFactory<(), ()> {
     #[inline(always)]
-    async fn unit_pre_middleware(request: Result<Request<String>>) -> Result<Request<String>> {
+    async fn unit_pre_middleware(request: Result<Request<Body>>) -> Result<Request<Body>> {
         request
     }
 
     #[inline(always)]
-    async fn unit_after_middleware(response: Result<Response<String>>) -> Result<Response<String>> {
+    async fn unit_after_middleware(response: Result<Response<Body>>) -> Result<Response<Body>> {
         response
     }
 
+    /// Create a [MiddlewareFactory] with middlewares that don't change the request or response
     pub fn new() -> MiddlewareFactory<
         impl PreMiddleware<
-            FutCallResponse = impl Future<Output = impl Into<InternalResult<Request<String>>>>,
+            FutCallResponse = impl Future<Output = impl Into<InternalResult<Request<Body>>>>,
         >,
         impl AfterMiddleware<
-            FutCallResponse = impl Future<Output = impl Into<InternalResult<Response<String>>>>,
+            FutCallResponse = impl Future<Output = impl Into<InternalResult<Response<Body>>>>,
         >,
     > {
         MiddlewareFactory {
             pre: Self::unit_pre_middleware,
             after: Self::unit_after_middleware,
+            arounds: Arounds::default(),
         }
     }
 }
@@ -86,23 +440,26 @@ where
     FPre: PreMiddleware<FutCallResponse = F>,
     FAfter: AfterMiddleware<FutCallResponse = FA>,
     F: Future<Output = CF> + Send,
-    CF: Into<InternalResult<Request<String>>> + Send,
-    CFA: Into<InternalResult<Response<String>>> + Send,
+    CF: Into<InternalResult<Request<Body>>> + Send,
+    CFA: Into<InternalResult<Response<Body>>> + Send,
     FA: Future<Output = CFA> + Send,
 {
+    /// Append a [PreMiddleware] to the chain
     #[inline(always)]
-    pub fn pre<NewF: Future<Output = NewCF>, NewCF: Into<InternalResult<Request<String>>>>(
+    pub fn pre<NewF: Future<Output = NewCF>, NewCF: Into<InternalResult<Request<Body>>>>(
         self,
-        other_pre: impl PreMiddleware<FutCallResponse = NewF> + Sync + Copy,
+        other_pre: impl PreMiddleware<FutCallResponse = NewF>,
     ) -> MiddlewareFactory<impl PreMiddleware<FutCallResponse = impl Future<Output = NewCF>>, FAfter>
     {
-        let pre = move |req: Result<Request<String>>| {
-            let cloned_pre_middleware = self.pre;
+        let previous_pre = self.pre;
+        let pre = move |req: Result<Request<Body>>| {
+            let cloned_pre_middleware = previous_pre.clone();
+            let other_pre = other_pre.clone();
             async move {
                 let resp = cloned_pre_middleware
                     .call(req.into())
                     .await;
-                let resp_internal_result: InternalResult<Request<String>> = resp.into();
+                let resp_internal_result: InternalResult<Request<Body>> = resp.into();
                 other_pre
                     .call(resp_internal_result)
                     .await
@@ -112,17 +469,21 @@ where
         MiddlewareFactory {
             pre,
             after: self.after,
+            arounds: self.arounds,
         }
     }
 
+    /// Append an [AfterMiddleware] to the chain
     #[inline(always)]
-    pub fn after<NewF: Future<Output = NewCFA>, NewCFA: Into<InternalResult<Response<String>>>>(
+    pub fn after<NewF: Future<Output = NewCFA>, NewCFA: Into<InternalResult<Response<Body>>>>(
         self,
-        other_after: impl AfterMiddleware<FutCallResponse = NewF> + Sync + Copy,
+        other_after: impl AfterMiddleware<FutCallResponse = NewF>,
     ) -> MiddlewareFactory<FPre, impl AfterMiddleware<FutCallResponse = impl Future<Output = NewCFA>>>
     {
-        let after = move |res: Result<Response<String>>| {
-            let cloned_after_middleware = self.after;
+        let previous_after = self.after;
+        let after = move |res: Result<Response<Body>>| {
+            let cloned_after_middleware = previous_after.clone();
+            let other_after = other_after.clone();
             async move {
                 let resp = cloned_after_middleware
                     .call(res.into())
@@ -136,31 +497,63 @@ where
         MiddlewareFactory {
             pre: self.pre,
             after,
+            arounds: self.arounds,
         }
     }
 
+    /// Append an [AroundMiddleware] to the chain
+    ///
+    /// [AroundMiddlewares](AroundMiddleware) run after every [PreMiddleware] and before every
+    /// [AfterMiddleware], in the order they are appended
+    pub fn around<NewAroundM, NewF, NewCF>(mut self, middleware: NewAroundM) -> Self
+    where
+        NewAroundM: AroundMiddleware<FutCallResponse = NewF>,
+        NewF: Future<Output = NewCF> + Send + 'static,
+        NewCF: Into<InternalResult<Response<Body>>>,
+    {
+        let middleware = Arc::new(middleware);
+        self.arounds
+            .0
+            .push(Arc::new(move |req, next| {
+                let resp = middleware.call(req, next);
+                Box::pin(async move { resp.await.into() })
+            }));
+
+        self
+    }
+
+    /// Wrap the handler with the chain of middlewares
+    #[allow(clippy::type_complexity)]
     pub fn build<R, FnInput, FnOutput, Deserializer, Serializer>(
         self: Arc<Self>,
         _runner: R,
         _deserializer: &Deserializer,
         _serializer: &Serializer,
-    ) -> impl Runner<(Result<Request<String>>, String), (Result<Response<String>>, String)>
+    ) -> impl Runner<(Extractors<(Result<Request<Body>>,)>, ()), (Returns<Result<Response<Body>>, ()>, ())>
     where
         R: Runner<(FnInput, Deserializer), (FnOutput, Serializer)> + 'static,
     {
-        move |req: Result<Request<String>>| {
-            let pre = self.pre;
-            let after = self.after;
+        let arounds = (!self.arounds.0.is_empty()).then(|| self.chain_arounds(_runner.clone()));
+
+        move |req: Result<Request<Body>>| {
+            let pre = self.pre.clone();
+            let after = self.after.clone();
             let runner = _runner.clone();
+            let arounds = arounds.clone();
             async move {
-                let req_updated: InternalResult<Request<String>> = pre
+                let req_updated: InternalResult<Request<Body>> = pre
                     .call(req.into_inner())
                     .await
                     .into();
-                let runner_resp = runner
-                    .call_runner(req_updated)
-                    .await;
-                let runner_resp_updated: InternalResult<Response<String>> = after
+                let runner_resp = match arounds {
+                    Some(arounds) => arounds(req_updated).await,
+                    None => {
+                        runner
+                            .call_runner(req_updated)
+                            .await
+                    }
+                };
+                let runner_resp_updated: InternalResult<Response<Body>> = after
                     .call(runner_resp)
                     .await
                     .into();
@@ -168,28 +561,66 @@ where
             }
         }
     }
+
+    /// Chain the [AroundMiddlewares](AroundMiddleware) around the `runner`
+    fn chain_arounds<R, FnInput, FnOutput, Deserializer, Serializer>(&self, runner: R) -> BoxedNext
+    where
+        R: Runner<(FnInput, Deserializer), (FnOutput, Serializer)> + 'static,
+    {
+        let handler: BoxedNext = Arc::new(move |req| {
+            let runner = runner.clone();
+            Box::pin(async move { runner.call_runner(req).await })
+        });
+
+        self.arounds
+            .0
+            .iter()
+            .rev()
+            .fold(handler, |next, around| {
+                let around = around.clone();
+                Arc::new(move |req| around(req, Next(next.clone())))
+            })
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
 
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use crate::{
-        error::Error, handler::Runner, middleware::MiddlewareFactory, request::Request,
-        response::Response, result::Result,
+        body::Body,
+        error::Error,
+        handler::Runner,
+        middleware::{Middleware, MiddlewareFactory, Next, Stateful, Timeout},
+        request::Request,
+        response::Response,
+        result::Result,
     };
 
-    async fn pre_middleware(_req: Result<Request<String>>) -> Result<Request<String>> {
-        Ok(Request::new(format!(
-            "{}\nFrom middleware",
+    async fn body_string(body: Body) -> String {
+        String::from_utf8(
+            body.bytes()
+                .await
+                .unwrap()
+                .to_vec(),
+        )
+        .unwrap()
+    }
+
+    async fn pre_middleware(_req: Result<Request<Body>>) -> Result<Request<Body>> {
+        let body = body_string(
             _req.into_inner()
                 .unwrap()
-                .body()
-        )))
-        .into()
+                .into_body(),
+        )
+        .await;
+
+        Ok(Request::new(format!("{}\nFrom middleware", body).into())).into()
     }
 
-    async fn pre_middleware_with_error(_req: Result<Request<String>>) -> Result<Request<String>> {
+    async fn pre_middleware_with_error(_req: Result<Request<Body>>) -> Result<Request<Body>> {
         Err(Error::new(
             "From middleware short-circuiting".to_owned(),
             200,
@@ -197,7 +628,7 @@ mod test {
         .into()
     }
 
-    async fn pre_middleware_error_handler(_: Result<Request<String>>) -> Result<Request<String>> {
+    async fn pre_middleware_error_handler(_: Result<Request<Body>>) -> Result<Request<Body>> {
         Err(Error::new("Error handled".to_owned(), 400)).into()
     }
 
@@ -205,34 +636,36 @@ mod test {
         Response::new(format!("{}\nFrom the handler", _req.body()))
     }
 
-    async fn after_middleware(res: Result<Response<String>>) -> Result<Response<String>> {
-        Ok(Response::new(format!(
-            "{}\nFrom the after middleware",
+    async fn after_middleware(res: Result<Response<Body>>) -> Result<Response<Body>> {
+        let body = body_string(
             res.into_inner()
                 .unwrap()
-                .body()
-        )))
+                .into_body(),
+        )
+        .await;
+
+        Ok(Response::new(
+            format!("{}\nFrom the after middleware", body).into(),
+        ))
         .into()
     }
 
-    async fn after_middleware_with_error(
-        res: Result<Response<String>>,
-    ) -> Result<Response<String>> {
+    async fn after_middleware_with_error(res: Result<Response<Body>>) -> Result<Response<Body>> {
+        let body = body_string(
+            res.into_inner()
+                .unwrap()
+                .into_body(),
+        )
+        .await;
+
         Err(Error::new(
-            format!(
-                "{}\nFrom middleware short-circuiting",
-                res.into_inner()
-                    .unwrap()
-                    .body()
-            ),
+            format!("{}\nFrom middleware short-circuiting", body),
             200,
         ))
         .into()
     }
 
-    async fn after_middleware_error_handler(
-        res: Result<Response<String>>,
-    ) -> Result<Response<String>> {
+    async fn after_middleware_error_handler(res: Result<Response<Body>>) -> Result<Response<Body>> {
         res.into_inner()
             .map_err(|_| Error::new("Error handled on after error".to_owned(), 400))
             .into()
@@ -261,10 +694,10 @@ mod test {
         );
 
         let resp = updated_handler
-            .call_runner(Request::new("From pure request".to_owned()).into())
+            .call_runner(Request::new("From pure request".into()).into())
             .await;
 
-        assert!(resp.unwrap().body() == "From pure request\nFrom middleware\nFrom the handler\nFrom the after middleware");
+        assert!(resp.unwrap().body().as_bytes().unwrap() == "From pure request\nFrom middleware\nFrom the handler\nFrom the after middleware");
 
         Ok(())
     }
@@ -283,7 +716,7 @@ mod test {
         );
 
         let resp = updated_handler
-            .call_runner(Request::new("From pure request".to_owned()).into())
+            .call_runner(Request::new("From pure request".into()).into())
             .await;
 
         assert!(resp.unwrap_err().body() == "From middleware short-circuiting");
@@ -306,7 +739,7 @@ mod test {
         );
 
         let resp = updated_handler
-            .call_runner(Request::new("From pure request".to_owned()).into())
+            .call_runner(Request::new("From pure request".into()).into())
             .await;
 
         assert!(resp.unwrap_err().body() == "From pure request\nFrom middleware\nFrom the handler\nFrom middleware short-circuiting");
@@ -329,7 +762,7 @@ mod test {
         );
 
         let resp = updated_handler
-            .call_runner(Request::new("From pure request".to_owned()).into())
+            .call_runner(Request::new("From pure request".into()).into())
             .await;
 
         assert!(resp.unwrap_err().body() == "Error handled");
@@ -353,7 +786,7 @@ mod test {
         );
 
         let resp = updated_handler
-            .call_runner(Request::new("From pure request".to_owned()).into())
+            .call_runner(Request::new("From pure request".into()).into())
             .await;
 
         assert!(
@@ -378,11 +811,295 @@ mod test {
             arc_middleware.build(runner_with_error, &(), &String::with_capacity(0));
 
         let resp = updated_handler
-            .call_runner(Request::new("From pure request".to_owned()).into())
+            .call_runner(Request::new("From pure request".into()).into())
             .await;
 
         assert!(resp.unwrap_err().body() == "Error handled on after error");
 
         Ok(())
     }
+
+    async fn around_middleware(req: Result<Request<Body>>, next: Next) -> Result<Response<Body>> {
+        let body = body_string(
+            req.into_inner()
+                .unwrap()
+                .into_body(),
+        )
+        .await;
+
+        let res = next
+            .run(
+                Ok(Request::new(
+                    format!("{}\nFrom the around middleware", body).into(),
+                ))
+                .into(),
+            )
+            .await;
+        let body = body_string(
+            res.into_inner()
+                .unwrap()
+                .into_body(),
+        )
+        .await;
+
+        Ok(Response::new(
+            format!("{}\nBack to the around middleware", body).into(),
+        ))
+        .into()
+    }
+
+    #[tokio::test]
+    async fn test_around_middleware() -> std::io::Result<()> {
+        let middleware = MiddlewareFactory::new()
+            .pre(&pre_middleware)
+            .after(&after_middleware)
+            .around(around_middleware);
+        let arc_middleware = Arc::new(middleware);
+
+        let updated_handler = arc_middleware.build(
+            test_handler,
+            &String::with_capacity(0),
+            &String::with_capacity(0),
+        );
+
+        let resp = updated_handler
+            .call_runner(Request::new("From pure request".into()).into())
+            .await;
+
+        assert_eq!(
+            resp.unwrap()
+                .body()
+                .as_bytes()
+                .unwrap(),
+            "From pure request\nFrom middleware\nFrom the around middleware\nFrom the handler\nBack to the around middleware\nFrom the after middleware"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_around_middleware_with_state() -> std::io::Result<()> {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let retry = move |_req: Result<Request<Body>>, next: Next| {
+            let counter = counter.clone();
+            async move {
+                loop {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let res = next
+                        .run(Ok(Request::new(Body::empty())).into())
+                        .await;
+                    if res.is_ok() || counter.load(Ordering::SeqCst) == 3 {
+                        return res;
+                    }
+                }
+            }
+        };
+
+        let arc_middleware = Arc::new(MiddlewareFactory::new().around(retry));
+        let updated_handler =
+            arc_middleware.build(runner_with_error, &(), &String::with_capacity(0));
+
+        let resp = updated_handler
+            .call_runner(Request::new("From pure request".into()).into())
+            .await;
+
+        assert!(resp.unwrap_err().body() == "From runner with short-circuiting");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        setups: AtomicUsize,
+        requests: AtomicUsize,
+        responses: AtomicUsize,
+    }
+
+    impl Middleware for Counter {
+        async fn setup(&self) -> Result<()> {
+            // The first setup fails, so it must run again on the next call
+            match self
+                .setups
+                .fetch_add(1, Ordering::SeqCst)
+            {
+                0 => Err(Error::new("Not ready".to_owned(), 503)).into(),
+                _ => Ok(()).into(),
+            }
+        }
+
+        async fn pre(&self, req: Result<Request<Body>>) -> Result<Request<Body>> {
+            self.requests
+                .fetch_add(1, Ordering::SeqCst);
+            req
+        }
+
+        async fn after(&self, res: Result<Response<Body>>) -> Result<Response<Body>> {
+            self.responses
+                .fetch_add(1, Ordering::SeqCst);
+            res
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stateful_middleware() -> std::io::Result<()> {
+        let counter = Stateful::new(Counter::default());
+        let arc_middleware = Arc::new(
+            MiddlewareFactory::new()
+                .pre(counter.clone())
+                .after(counter.clone()),
+        );
+
+        let updated_handler = arc_middleware.build(
+            test_handler,
+            &String::with_capacity(0),
+            &String::with_capacity(0),
+        );
+
+        let resp = updated_handler
+            .call_runner(Request::new("From pure request".into()).into())
+            .await;
+        assert_eq!(*resp.unwrap_err().code(), 503);
+
+        // The setup failed on the PreMiddleware, so the AfterMiddleware runs it again
+        assert_eq!(
+            counter
+                .0
+                .middleware
+                .setups
+                .load(Ordering::SeqCst),
+            2
+        );
+        assert_eq!(
+            counter
+                .0
+                .middleware
+                .responses
+                .load(Ordering::SeqCst),
+            1
+        );
+
+        for _ in 0..2 {
+            let resp = updated_handler
+                .call_runner(Request::new("From pure request".into()).into())
+                .await;
+            assert!(resp.is_ok());
+        }
+
+        assert_eq!(
+            counter
+                .0
+                .middleware
+                .setups
+                .load(Ordering::SeqCst),
+            2
+        );
+        assert_eq!(
+            counter
+                .0
+                .middleware
+                .requests
+                .load(Ordering::SeqCst),
+            2
+        );
+        assert_eq!(
+            counter
+                .0
+                .middleware
+                .responses
+                .load(Ordering::SeqCst),
+            3
+        );
+
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct ResponseCounter {
+        responses: AtomicUsize,
+    }
+
+    impl Middleware for ResponseCounter {
+        async fn after(&self, res: Result<Response<Body>>) -> Result<Response<Body>> {
+            self.responses
+                .fetch_add(1, Ordering::SeqCst);
+            res
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stateful_after_middleware_only() -> std::io::Result<()> {
+        let counter = Stateful::new(ResponseCounter::default());
+        let arc_middleware = Arc::new(MiddlewareFactory::new().after(counter.clone()));
+
+        let updated_handler = arc_middleware.build(
+            test_handler,
+            &String::with_capacity(0),
+            &String::with_capacity(0),
+        );
+
+        for _ in 0..2 {
+            let resp = updated_handler
+                .call_runner(Request::new("From pure request".into()).into())
+                .await;
+            assert!(resp.is_ok());
+        }
+
+        assert_eq!(
+            counter
+                .0
+                .middleware
+                .responses
+                .load(Ordering::SeqCst),
+            2
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout_cancels_the_handler() -> std::io::Result<()> {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let counter = finished.clone();
+        let slow_handler = move || {
+            let counter = counter.clone();
+            async move {
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+                String::from("Too late")
+            }
+        };
+
+        let arc_middleware = Arc::new(
+            MiddlewareFactory::new()
+                .after(&after_middleware_error_handler)
+                .around(Timeout::new(std::time::Duration::from_millis(20))),
+        );
+        let updated_handler =
+            arc_middleware
+                .clone()
+                .build(slow_handler.clone(), &(), &String::with_capacity(0));
+
+        let resp = updated_handler
+            .call_runner(Request::new("From pure request".into()).into())
+            .await;
+        assert!(resp.unwrap_err().body() == "Error handled on after error");
+
+        let arc_middleware = Arc::new(
+            MiddlewareFactory::new()
+                .around(Timeout::new(std::time::Duration::from_millis(20)).request_timeout()),
+        );
+        let updated_handler = arc_middleware.build(slow_handler, &(), &String::with_capacity(0));
+
+        let resp = updated_handler
+            .call_runner(Request::new("From pure request".into()).into())
+            .await;
+        assert_eq!(*resp.unwrap_err().code(), 408);
+
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        assert_eq!(finished.load(Ordering::SeqCst), 0);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,393 @@
+//! Extraction of `multipart/form-data` bodies
+//!
+//! Refeer to the [Multipart] for more information
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures::{future::Ready, Stream};
+
+use crate::{
+    body::Body,
+    error::Error,
+    middleware::PreMiddleware,
+    request::{HeaderMap, Parts, Request},
+    result::InternalResult,
+};
+
+/// Default limit of a whole `multipart/form-data` body, 8 MiB
+pub const DEFAULT_TOTAL_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// Extract a `multipart/form-data` body, reading its fields one at a time
+///
+/// Nothing is buffered unless it's asked for, so each [Field] can be streamed chunk by chunk.
+/// Being a body extractor, [Multipart] must be the last argument of the handler, and works with
+/// any deserializer:
+///
+/// ```rust
+/// use yahf::error::Error;
+/// use yahf::multipart::Multipart;
+/// use yahf::result::Result;
+///
+/// async fn upload(multipart: Multipart) -> Result<String> {
+///     sizes(multipart).await.into()
+/// }
+///
+/// async fn sizes(mut multipart: Multipart) -> std::result::Result<String, Error> {
+///     let mut uploaded = Vec::new();
+///
+///     while let Some(mut field) = multipart.next_field().await? {
+///         let name = field.name().unwrap_or_default().to_string();
+///         let file_name = field.file_name().unwrap_or_default().to_string();
+///
+///         let mut size = 0;
+///         while let Some(chunk) = field.chunk().await? {
+///             size += chunk.len();
+///         }
+///
+///         uploaded.push(format!("{}={} ({} bytes)", name, file_name, size));
+///     }
+///
+///     Ok(uploaded.join("\n"))
+/// }
+/// # let router = yahf::router::Router::new()
+/// #     .post("/upload", upload, &(), &String::with_capacity(0));
+/// ```
+///
+/// A request without a `multipart/form-data` `Content-Type` is answered with a `415 Unsupported
+/// Media Type`, and going over the [MultipartLimits] with a `413 Payload Too Large`
+pub struct Multipart(multer::Multipart<'static>);
+
+impl Multipart {
+    /// Read the next [Field], or [None] when there are no more fields
+    ///
+    /// The previous [Field] must be dropped before reading the next one
+    pub async fn next_field(&mut self) -> InternalResult<Option<Field>> {
+        self.0
+            .next_field()
+            .await
+            .map(|field| field.map(Field))
+            .map_err(into_error)
+    }
+
+    pub(crate) fn from_parts(parts: &Parts, body: Body) -> InternalResult<Self> {
+        let boundary = parts
+            .headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .and_then(|content_type| multer::parse_boundary(content_type).ok())
+            .ok_or_else(|| {
+                Error::new(
+                    "Expected a multipart/form-data body with a boundary".into(),
+                    415,
+                )
+                .with_error_code("unsupported_media_type")
+            })?;
+
+        let limits = parts
+            .extensions
+            .get::<MultipartLimits>()
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(Self(multer::Multipart::with_constraints(
+            body,
+            boundary,
+            multer::Constraints::new().size_limit(limits.into()),
+        )))
+    }
+}
+
+/// A field of a [Multipart] body, either a value or a file
+///
+/// The content of a [Field] is read through [Field::chunk], as a [Stream] of chunks, or all at
+/// once with [Field::bytes] and [Field::text]
+pub struct Field(multer::Field<'static>);
+
+impl Field {
+    /// Name of the field, from its `Content-Disposition`
+    pub fn name(&self) -> Option<&str> {
+        self.0.name()
+    }
+
+    /// File name of the field, from its `Content-Disposition`, when it's a file
+    pub fn file_name(&self) -> Option<&str> {
+        self.0.file_name()
+    }
+
+    /// `Content-Type` of the field
+    pub fn content_type(&self) -> Option<&str> {
+        self.0
+            .content_type()
+            .map(AsRef::as_ref)
+    }
+
+    /// Headers of the field
+    pub fn headers(&self) -> &HeaderMap {
+        self.0.headers()
+    }
+
+    /// Read the next chunk of the field, or [None] when it's over
+    pub async fn chunk(&mut self) -> InternalResult<Option<Bytes>> {
+        self.0
+            .chunk()
+            .await
+            .map_err(into_error)
+    }
+
+    /// Read the whole field
+    pub async fn bytes(self) -> InternalResult<Bytes> {
+        self.0
+            .bytes()
+            .await
+            .map_err(into_error)
+    }
+
+    /// Read the whole field as text
+    pub async fn text(self) -> InternalResult<String> {
+        self.0
+            .text()
+            .await
+            .map_err(into_error)
+    }
+}
+
+impl Stream for Field {
+    type Item = InternalResult<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.0)
+            .poll_next(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map_err(into_error)))
+    }
+}
+
+/// Size limits of a [Multipart] body
+///
+/// The limits are a [PreMiddleware], so they can be set for a whole
+/// [Server](crate::server::Server) or [Router](crate::router::Router), or for a single
+/// [Route](crate::router::Route):
+///
+/// ```rust
+/// use yahf::multipart::{Multipart, MultipartLimits};
+/// use yahf::router::Route;
+/// use yahf::server::Server;
+///
+/// async fn upload(multipart: Multipart) -> String
+/// # { String::new() }
+///
+/// let server = Server::new().route(
+///     "/upload",
+///     Route::new()
+///         .post(upload, &(), &String::with_capacity(0))
+///         .with_pre(
+///             MultipartLimits::new()
+///                 .total(64 * 1024 * 1024)
+///                 .per_field(1024)
+///                 .field("avatar", 16 * 1024 * 1024),
+///         ),
+/// );
+/// ```
+///
+/// By default, the whole body is limited to [DEFAULT_TOTAL_LIMIT] and each field only by the
+/// total. When more than one applies to a route, the one closest to the route wins
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartLimits {
+    total: Option<u64>,
+    per_field: Option<u64>,
+    fields: Vec<(String, u64)>,
+}
+
+impl Default for MultipartLimits {
+    fn default() -> Self {
+        Self {
+            total: Some(DEFAULT_TOTAL_LIMIT),
+            per_field: None,
+            fields: Vec::new(),
+        }
+    }
+}
+
+impl MultipartLimits {
+    /// Create the default [MultipartLimits]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the size of the whole body, in bytes
+    pub fn total(mut self, limit: u64) -> Self {
+        self.total = Some(limit);
+        self
+    }
+
+    /// Remove the limit of the whole body
+    pub fn unlimited(mut self) -> Self {
+        self.total = None;
+        self
+    }
+
+    /// Limit the size of every field, in bytes
+    pub fn per_field(mut self, limit: u64) -> Self {
+        self.per_field = Some(limit);
+        self
+    }
+
+    /// Limit the size of the field `name`, in bytes, overriding [MultipartLimits::per_field]
+    pub fn field(mut self, name: impl Into<String>, limit: u64) -> Self {
+        self.fields
+            .push((name.into(), limit));
+        self
+    }
+}
+
+impl PreMiddleware for MultipartLimits {
+    type FutCallResponse = Ready<InternalResult<Request<Body>>>;
+
+    fn call(&self, req: InternalResult<Request<Body>>) -> Self::FutCallResponse {
+        futures::future::ready(req.map(|mut req| {
+            req.extensions_mut()
+                .insert(self.clone());
+            req
+        }))
+    }
+}
+
+impl From<MultipartLimits> for multer::SizeLimit {
+    fn from(limits: MultipartLimits) -> Self {
+        let mut size_limit = multer::SizeLimit::new();
+        if let Some(total) = limits.total {
+            size_limit = size_limit.whole_stream(total);
+        }
+        if let Some(per_field) = limits.per_field {
+            size_limit = size_limit.per_field(per_field);
+        }
+
+        limits
+            .fields
+            .into_iter()
+            .fold(size_limit, |size_limit, (name, limit)| {
+                size_limit.for_field(name, limit)
+            })
+    }
+}
+
+fn into_error(err: multer::Error) -> Error {
+    match err {
+        // Errors of the body itself, like going over the body limit of the route, are kept
+        multer::Error::StreamReadFailed(source) if source.is::<Error>() => *source
+            .downcast::<Error>()
+            .expect("Checked the type of the source"),
+        // Going over the total limit is reported as a failure to read the stream
+        multer::Error::StreamReadFailed(source) if source.is::<multer::Error>() => into_error(
+            *source
+                .downcast::<multer::Error>()
+                .expect("Checked the type of the source"),
+        ),
+        multer::Error::FieldSizeExceeded { .. } | multer::Error::StreamSizeExceeded { .. } => {
+            Error::new(format!("Multipart body too large: {}", err), 413)
+                .with_error_code("payload_too_large")
+        }
+        err => Error::new(format!("Invalid multipart body: {}", err), 400)
+            .with_error_code("invalid_multipart"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::request::{HttpBuilder, Request};
+
+    use super::{Multipart, MultipartLimits};
+
+    const BODY: &str = "--X\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        Holidays\r\n\
+        --X\r\n\
+        Content-Disposition: form-data; name=\"photo\"; filename=\"beach.png\"\r\n\
+        Content-Type: image/png\r\n\r\n\
+        PNG bytes\r\n\
+        --X--\r\n";
+
+    fn multipart(content_type: &str, limits: Option<MultipartLimits>) -> Result<Multipart, u16> {
+        let mut req: Request<_> = HttpBuilder::new()
+            .header(http::header::CONTENT_TYPE, content_type)
+            .body(BODY.into())
+            .unwrap()
+            .into();
+        if let Some(limits) = limits {
+            req.extensions_mut()
+                .insert(limits);
+        }
+
+        let (parts, body) = req.into_parts();
+        Multipart::from_parts(&parts, body).map_err(|err| *err.code())
+    }
+
+    #[tokio::test]
+    async fn test_fields_and_metadata() {
+        let mut multipart = multipart("multipart/form-data; boundary=X", None).unwrap();
+
+        let field = multipart
+            .next_field()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(field.name(), Some("title"));
+        assert_eq!(field.file_name(), None);
+        assert_eq!(field.text().await.unwrap(), "Holidays");
+
+        let field = multipart
+            .next_field()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(field.name(), Some("photo"));
+        assert_eq!(field.file_name(), Some("beach.png"));
+        assert_eq!(field.content_type(), Some("image/png"));
+        assert_eq!(&field.bytes().await.unwrap()[..], b"PNG bytes");
+
+        assert!(multipart
+            .next_field()
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_missing_boundary() {
+        assert_eq!(multipart("application/json", None).err(), Some(415));
+        assert_eq!(multipart("multipart/form-data", None).err(), Some(415));
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let mut multipart = multipart(
+            "multipart/form-data; boundary=X",
+            Some(MultipartLimits::new().field("photo", 4)),
+        )
+        .unwrap();
+
+        let field = multipart
+            .next_field()
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(field.text().await.is_ok());
+
+        let field = multipart
+            .next_field()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            field
+                .bytes()
+                .await
+                .err()
+                .map(|err| *err.code()),
+            Some(413)
+        );
+    }
+}
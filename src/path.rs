@@ -0,0 +1,467 @@
+//! Storage and [serde](serde) support for the values captured from `{param}` segments
+
+use std::fmt::Display;
+
+use percent_encoding::percent_decode_str;
+use serde::de::{
+    self, value::StrDeserializer, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer,
+    MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+
+use crate::{error::Error, result::InternalResult};
+
+/// Values captured from the `{param}` segments of the matched route, in declaration order
+///
+/// The values are kept as they were received, and percent-decoded when deserialized
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PathParams(Vec<(String, String)>);
+
+impl PathParams {
+    pub(crate) fn new(params: Vec<(String, String)>) -> Self {
+        Self(params)
+    }
+
+    pub(crate) fn deserialize<T: DeserializeOwned>(&self) -> InternalResult<T> {
+        let params = self
+            .0
+            .iter()
+            .map(|(name, value)| {
+                percent_decode_str(value)
+                    .decode_utf8()
+                    .map(|value| (name.clone(), value.into_owned()))
+                    .map_err(|_| {
+                        Error::new(
+                            format!("Invalid path parameters: {} is not valid UTF-8", name),
+                            400,
+                        )
+                    })
+            })
+            .collect::<InternalResult<Vec<_>>>()?;
+
+        T::deserialize(PathDeserializer { params: &params })
+            .map_err(|err| Error::new(format!("Invalid path parameters: {}", err.0), 400))
+    }
+}
+
+#[derive(Debug)]
+struct PathDeserializationError(String);
+
+impl Display for PathDeserializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PathDeserializationError {}
+
+impl de::Error for PathDeserializationError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+struct PathDeserializer<'de> {
+    params: &'de [(String, String)],
+}
+
+impl<'de> PathDeserializer<'de> {
+    fn single_value(&self) -> Result<ValueDeserializer<'de>, PathDeserializationError> {
+        match self.params {
+            [(_, value)] => Ok(ValueDeserializer(value)),
+            params => Err(de::Error::custom(format!(
+                "expected 1 parameter, found {}",
+                params.len()
+            ))),
+        }
+    }
+}
+
+macro_rules! forward_to_single_value {
+    ($($method: ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.single_value()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for PathDeserializer<'de> {
+    type Error = PathDeserializationError;
+
+    forward_to_single_value!(
+        deserialize_bool,
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_i128,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_u128,
+        deserialize_f32,
+        deserialize_f64,
+        deserialize_char,
+        deserialize_str,
+        deserialize_string,
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_option,
+        deserialize_identifier
+    );
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.params.len() {
+            1 => self
+                .single_value()?
+                .deserialize_any(visitor),
+            _ => self.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(ParamsSeqAccess {
+            params: self.params.iter(),
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.params.len() != len {
+            return Err(de::Error::custom(format!(
+                "expected {} parameters, found {}",
+                len,
+                self.params.len()
+            )));
+        }
+
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(ParamsMapAccess {
+            params: self.params.iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.single_value()?
+            .deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+}
+
+struct ParamsSeqAccess<'de> {
+    params: std::slice::Iter<'de, (String, String)>,
+}
+
+impl<'de> SeqAccess<'de> for ParamsSeqAccess<'de> {
+    type Error = PathDeserializationError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        self.params
+            .next()
+            .map(|(_, value)| seed.deserialize(ValueDeserializer(value)))
+            .transpose()
+    }
+}
+
+struct ParamsMapAccess<'de> {
+    params: std::slice::Iter<'de, (String, String)>,
+    value: Option<&'de str>,
+}
+
+impl<'de> MapAccess<'de> for ParamsMapAccess<'de> {
+    type Error = PathDeserializationError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.params.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                let key: StrDeserializer<'de, Self::Error> = key
+                    .as_str()
+                    .into_deserializer();
+                seed.deserialize(key)
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(ValueDeserializer(value)),
+            None => Err(de::Error::custom("value requested before key")),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ValueDeserializer<'de>(&'de str);
+
+macro_rules! parse_value {
+    ($($method: ident => $visit: ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.0.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(err) => Err(de::Error::custom(format!("`{}`: {}", self.0, err))),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = PathDeserializationError;
+
+    parse_value!(
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char
+    );
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+impl<'de> EnumAccess<'de> for ValueDeserializer<'de> {
+    type Error = PathDeserializationError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        seed.deserialize(self)
+            .map(|value| (value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for ValueDeserializer<'de> {
+    type Error = PathDeserializationError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        _seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        Err(de::Error::custom("only unit variants are supported"))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only unit variants are supported"))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only unit variants are supported"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::PathParams;
+
+    fn params(values: &[(&str, &str)]) -> PathParams {
+        PathParams::new(
+            values
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct UserPost {
+        user_id: u32,
+        slug: String,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Order {
+        Asc,
+        Desc,
+    }
+
+    #[test]
+    fn test_deserialize_single_value() {
+        let id: u64 = params(&[("id", "42")])
+            .deserialize()
+            .unwrap();
+
+        assert_eq!(id, 42);
+    }
+
+    #[test]
+    fn test_deserialize_struct() {
+        let post: UserPost = params(&[("user_id", "7"), ("slug", "hello-world")])
+            .deserialize()
+            .unwrap();
+
+        assert_eq!(
+            post,
+            UserPost {
+                user_id: 7,
+                slug: "hello-world".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_tuple_and_enum() {
+        let (id, order): (u32, Order) = params(&[("id", "1"), ("order", "desc")])
+            .deserialize()
+            .unwrap();
+
+        assert_eq!((id, order), (1, Order::Desc));
+    }
+
+    #[test]
+    fn test_invalid_value_is_a_bad_request() {
+        let err = params(&[("id", "not-a-number")])
+            .deserialize::<u32>()
+            .unwrap_err();
+
+        assert_eq!(err.code(), &400);
+    }
+
+    #[test]
+    fn test_values_are_percent_decoded() {
+        let (name, tag): (String, String) = params(&[("name", "john%20doe"), ("tag", "a%2Fb")])
+            .deserialize()
+            .unwrap();
+
+        assert_eq!((name.as_str(), tag.as_str()), ("john doe", "a/b"));
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_a_bad_request() {
+        let err = params(&[("name", "john%FFdoe")])
+            .deserialize::<String>()
+            .unwrap_err();
+
+        assert_eq!(err.code(), &400);
+    }
+
+    #[test]
+    fn test_wrong_number_of_params_is_a_bad_request() {
+        let err = params(&[("id", "1"), ("other", "2")])
+            .deserialize::<u32>()
+            .unwrap_err();
+
+        assert_eq!(err.code(), &400);
+    }
+}
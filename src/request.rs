@@ -1,10 +1,11 @@
 //! Newtype of [Request](http::Request)
 use std::ops::{Deref, DerefMut};
 
-use crate::result::InternalResult;
+use crate::{body::Body, result::InternalResult};
 
 pub use http::request::Builder as HttpBuilder;
 pub use http::request::Parts;
+pub use http::HeaderMap;
 pub use http::HeaderName;
 pub use http::HeaderValue;
 pub use http::Method;
@@ -52,15 +53,6 @@ impl<T> Request<T> {
         Request(HttpRequest::from_parts(parts, body))
     }
 
-    // TODO: Valuate if this will keep this fn or move to an from_parts style
-    pub(crate) fn and_then<BodyType>(
-        self,
-        callback: impl FnOnce(T) -> InternalResult<BodyType>,
-    ) -> InternalResult<Request<BodyType>> {
-        let (parts, body) = self.0.into_parts();
-        callback(body).map(|body| Request(HttpRequest::from_parts(parts, body)))
-    }
-
     /// Consume [NewType](crate::request::Request) and Return the original [Request](http::Request)
     pub fn into_inner(self) -> HttpRequest<T> {
         self.0
@@ -111,10 +103,10 @@ impl Builder {
         }
     }
 
-    pub(crate) fn body<T>(self, body: T) -> Request<T> {
+    pub(crate) fn body(self, body: impl Into<Body>) -> Request<Body> {
         Request(
             self.builder
-                .body(body)
+                .body(body.into())
                 .unwrap(),
         )
     }
@@ -152,8 +144,8 @@ impl<T> From<http::Request<T>> for Request<T> {
     }
 }
 
-impl From<Request<String>> for InternalResult<Request<String>> {
-    fn from(val: Request<String>) -> Self {
+impl From<Request<Body>> for InternalResult<Request<Body>> {
+    fn from(val: Request<Body>) -> Self {
         Ok(val)
     }
 }
@@ -1,11 +1,13 @@
 //! NewType of [Response](http::Response)
 use std::ops::{Deref, DerefMut};
 
-use crate::result::InternalResult;
+use crate::{body::Body, result::InternalResult};
 
 pub use http::response::Builder as HttpResponseBuilder;
 use http::response::Parts;
+pub use http::HeaderMap;
 pub use http::Response as HttpResponse;
+pub use http::StatusCode;
 
 /// NewType of [Response](http::Response)
 #[derive(Debug)]
@@ -101,8 +103,240 @@ impl<T> Response<T> {
     }
 }
 
-impl From<Response<String>> for InternalResult<Response<String>> {
-    fn from(val: Response<String>) -> Self {
+impl From<Response<Body>> for InternalResult<Response<Body>> {
+    fn from(val: Response<Body>) -> Self {
         Ok(val)
     }
 }
+
+/// Convert a value returned by a [`handler`](crate::handler::Runner) into a [Response]
+///
+/// The body of the [Response] goes through the [`Serializer`](crate::serializer::BodySerializer)
+/// of the route, unless it's already a [Body]. Out of the box, handlers can return:
+///
+/// - `(StatusCode, T)`, to set the status
+/// - `(StatusCode, HeaderMap, T)`, to set the status and append headers, keeping every value
+///   of a header that's already set
+/// - [StatusCode], to answer without a body
+/// - [Redirect], to redirect to another location
+///
+/// ```rust
+/// # use serde::Serialize;
+/// use yahf::handler::Json;
+/// use yahf::response::{IntoResponse, Response, StatusCode};
+/// use yahf::router::Router;
+///
+/// #[derive(Serialize)]
+/// struct User { id: u64 }
+///
+/// async fn create_user() -> (StatusCode, User) {
+///     (StatusCode::CREATED, User { id: 1 })
+/// }
+///
+/// // A type of our own, that knows how to become a response
+/// struct Created(User);
+///
+/// impl IntoResponse for Created {
+///     type Body = User;
+///
+///     fn into_response(self) -> Response<User> {
+///         let mut response = Response::new(self.0);
+///         *response.status_mut() = StatusCode::CREATED;
+///         response
+///     }
+/// }
+///
+/// async fn create_user_again() -> Created {
+///     Created(User { id: 2 })
+/// }
+///
+/// let router = Router::new()
+///     .post("/users", create_user, &(), &Json::new())
+///     .put("/users", create_user_again, &(), &Json::new());
+/// ```
+pub trait IntoResponse {
+    /// Type of the body, serialized by the route
+    /// [`Serializer`](crate::serializer::BodySerializer)
+    type Body;
+
+    /// Convert the value into a [Response]
+    fn into_response(self) -> Response<Self::Body>;
+}
+
+impl<T> IntoResponse for (StatusCode, T) {
+    type Body = T;
+
+    fn into_response(self) -> Response<T> {
+        let (status, body) = self;
+
+        let mut response = Response::new(body);
+        *response.status_mut() = status;
+        response
+    }
+}
+
+impl<T> IntoResponse for (StatusCode, HeaderMap, T) {
+    type Body = T;
+
+    fn into_response(self) -> Response<T> {
+        let (status, headers, body) = self;
+
+        let mut response = (status, body).into_response();
+        append_headers(&mut response, headers);
+        response
+    }
+}
+
+/// Append the `headers` to the ones of the `response`, unlike [HeaderMap::extend] that replaces
+/// the values of the headers already set
+fn append_headers<T>(response: &mut Response<T>, headers: HeaderMap) {
+    let mut name = None;
+    for (key, value) in headers {
+        // Only the first value of each header comes with its name
+        if key.is_some() {
+            name = key;
+        }
+        if let Some(name) = &name {
+            response
+                .headers_mut()
+                .append(name.clone(), value);
+        }
+    }
+}
+
+impl IntoResponse for StatusCode {
+    type Body = Body;
+
+    fn into_response(self) -> Response<Body> {
+        (self, Body::empty()).into_response()
+    }
+}
+
+/// Redirect the request to another location, with an empty body
+///
+/// A location that isn't a valid header value, like one with a line break, can't be sent in the
+/// `Location` header, so it's answered with a `500 Internal Server Error` instead
+///
+/// ```rust
+/// # use yahf::router::Router;
+/// use yahf::response::Redirect;
+///
+/// async fn old_users() -> Redirect {
+///     Redirect::permanent("/users")
+/// }
+///
+/// let router = Router::new().get("/old-users", old_users, &(), &());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    status: StatusCode,
+    location: Option<http::HeaderValue>,
+}
+
+impl Redirect {
+    /// Redirect with `303 See Other`, so the location is requested with `GET`
+    pub fn to(location: impl Into<String>) -> Self {
+        Self::with_status(StatusCode::SEE_OTHER, location)
+    }
+
+    /// Redirect with `307 Temporary Redirect`, keeping the method and the body
+    pub fn temporary(location: impl Into<String>) -> Self {
+        Self::with_status(StatusCode::TEMPORARY_REDIRECT, location)
+    }
+
+    /// Redirect with `308 Permanent Redirect`, keeping the method and the body
+    pub fn permanent(location: impl Into<String>) -> Self {
+        Self::with_status(StatusCode::PERMANENT_REDIRECT, location)
+    }
+
+    fn with_status(status: StatusCode, location: impl Into<String>) -> Self {
+        Self {
+            status,
+            location: http::HeaderValue::try_from(location.into()).ok(),
+        }
+    }
+}
+
+impl IntoResponse for Redirect {
+    type Body = Body;
+
+    fn into_response(self) -> Response<Body> {
+        let Some(location) = self.location else {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Body::from("Invalid redirect location"),
+            )
+                .into_response();
+        };
+
+        let mut response = self.status.into_response();
+        response
+            .headers_mut()
+            .insert(http::header::LOCATION, location);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{header::SET_COOKIE, HeaderMap, HeaderValue, StatusCode};
+
+    use crate::body::Body;
+
+    use super::{append_headers, IntoResponse, Redirect, Response};
+
+    #[test]
+    fn test_headers_are_appended() {
+        let mut response = Response::new(Body::empty());
+        response
+            .headers_mut()
+            .insert(SET_COOKIE, HeaderValue::from_static("session=1"));
+
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, HeaderValue::from_static("theme=dark"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("lang=en"));
+        append_headers(&mut response, headers);
+
+        let cookies: Vec<_> = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .collect();
+        assert_eq!(cookies, ["session=1", "theme=dark", "lang=en"]);
+    }
+
+    #[test]
+    fn test_status_and_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, HeaderValue::from_static("theme=dark"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("lang=en"));
+
+        let response = (StatusCode::CREATED, headers, "Created").into_response();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response
+                .headers()
+                .get_all(SET_COOKIE)
+                .iter()
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_invalid_redirect_location() {
+        let response = Redirect::to("/home\r\nx-injected: true").into_response();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response
+            .headers()
+            .get(http::header::LOCATION)
+            .is_none());
+
+        let response = Redirect::permanent("/home").into_response();
+
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[http::header::LOCATION], "/home");
+    }
+}
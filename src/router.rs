@@ -6,11 +6,17 @@ use std::sync::Arc;
 use futures::Future;
 
 use crate::{
-    handler::{encapsulate_runner, RefHandler, Runner},
-    middleware::{AfterMiddleware, MiddlewareFactory, PreMiddleware},
+    body::Body,
+    fallback::Fallbacks,
+    handler::{encapsulate_runner, BoxedHandler, RefHandler, Runner},
+    limits::BodyLimit,
+    middleware::{AfterMiddleware, AroundMiddleware, MiddlewareFactory, PreMiddleware},
+    path::PathParams,
     request::{Method, Request},
     response::Response,
     result::InternalResult,
+    runner_input::RequiredState,
+    state::{Requirements, States},
     tree::RouterTree,
 };
 
@@ -23,6 +29,7 @@ use crate::{
 /// ```rust
 ///# use serde::Deserialize;
 ///# use serde::Serialize;
+///# use yahf::body::Body;
 ///# use yahf::handler::Json;
 ///# use yahf::request::Request;
 ///# use yahf::result::Result;
@@ -37,7 +44,7 @@ use crate::{
 /// }
 ///
 /// // Print the time, the method, and the path from the Request
-/// async fn log_middleware(req: Result<Request<String>>) -> Result<Request<String>>
+/// async fn log_middleware(req: Result<Request<Body>>) -> Result<Request<Body>>
 ///# {
 ///#     match req.into_inner() {
 ///#        Ok(req) => {
@@ -58,7 +65,7 @@ use crate::{
 ///# }
 ///
 /// // Handle any possible errors
-/// async fn log_error(res: Result<Response<String>>) -> Result<Response<String>>
+/// async fn log_error(res: Result<Response<Body>>) -> Result<Response<Body>>
 ///# {
 ///#    match res.into_inner() {
 ///#        Err(err) => {
@@ -97,6 +104,9 @@ use crate::{
 /// ```
 pub struct Router<PreM, AfterM> {
     middleware_factory: Arc<MiddlewareFactory<PreM, AfterM>>,
+    states: States,
+    required_states: Requirements,
+    fallbacks: Fallbacks,
     get: RouterTree<'static>,
     put: RouterTree<'static>,
     delete: RouterTree<'static>,
@@ -108,18 +118,24 @@ pub struct Router<PreM, AfterM> {
     head: RouterTree<'static>,
 }
 
+/// Wrapper applied to the [BoxedHandler] of a route
+type Layer = Arc<dyn Fn(BoxedHandler) -> BoxedHandler + Send + Sync>;
+
 impl Router<(), ()> {
     /// Create a new [Router]
     pub fn new() -> Router<
         impl PreMiddleware<
-            FutCallResponse = impl Future<Output = impl Into<InternalResult<Request<String>>>>,
+            FutCallResponse = impl Future<Output = impl Into<InternalResult<Request<Body>>>>,
         >,
         impl AfterMiddleware<
-            FutCallResponse = impl Future<Output = impl Into<InternalResult<Response<String>>>>,
+            FutCallResponse = impl Future<Output = impl Into<InternalResult<Response<Body>>>>,
         >,
     > {
         Router {
             middleware_factory: Arc::new(MiddlewareFactory::new()),
+            states: States::default(),
+            required_states: Requirements::default(),
+            fallbacks: Fallbacks::default(),
             get: RouterTree::new(),
             put: RouterTree::new(),
             delete: RouterTree::new(),
@@ -167,13 +183,8 @@ macro_rules! method_insert {
                 .clone()
                 .build(handler, deserializer, serializer);
 
-            self.method(
-                $method,
-                path,
-                built_with_middleware,
-                &String::with_capacity(0),
-                &String::with_capacity(0),
-            )
+            self.method($method, path, built_with_middleware, &(), &())
+                .require_states($method, path, R::required_states())
         }
     };
 }
@@ -182,16 +193,17 @@ impl<PreM, AfterM, FutP, FutA, ResultP, ResultA> Router<PreM, AfterM>
 where
     PreM: PreMiddleware<FutCallResponse = FutP> + 'static,
     FutP: Future<Output = ResultP> + std::marker::Send + 'static,
-    ResultP: Into<InternalResult<Request<String>>> + std::marker::Send + 'static,
+    ResultP: Into<InternalResult<Request<Body>>> + std::marker::Send + 'static,
     AfterM: AfterMiddleware<FutCallResponse = FutA> + 'static,
     FutA: Future<Output = ResultA> + std::marker::Send + 'static,
-    ResultA: Into<InternalResult<Response<String>>> + std::marker::Send + 'static,
+    ResultA: Into<InternalResult<Response<Body>>> + std::marker::Send + 'static,
 {
     /// Extend a [Router] with another one and return the new [Router]
     ///
     /// A example:
     ///
     /// ```rust
+    /// # use yahf::body::Body;
     /// # use yahf::request::Request;
     /// # use yahf::router::Router;
     ///# use yahf::result::Result;
@@ -202,7 +214,7 @@ where
     /// # #[derive(Deserialize, Serialize)]
     /// # struct Computation { value: u64 }
     /// #
-    /// async fn logger(req: Result<Request<String>>) -> Result<Request<String>>
+    /// async fn logger(req: Result<Request<Body>>) -> Result<Request<Body>>
     /// # { req }
     /// #
     /// async fn some_computation(req: Computation) -> Computation
@@ -223,7 +235,47 @@ where
     /// routes of B, adding B routes to A and then concatenating A's middlewares with B's
     /// middlewares
     pub fn router<OtherPreM, OtherAfterM, OtherFutA, OtherFutP, OtherResultP, OtherResultA>(
+        self,
+        router: Router<OtherPreM, OtherAfterM>,
+    ) -> Router<PreM, AfterM>
+    where
+        OtherPreM: PreMiddleware<FutCallResponse = OtherFutP> + 'static,
+        OtherAfterM: AfterMiddleware<FutCallResponse = OtherFutA> + 'static,
+        OtherFutP: Future<Output = OtherResultP> + Send,
+        OtherFutA: Future<Output = OtherResultA> + Send,
+        OtherResultP: Into<InternalResult<Request<Body>>> + Send,
+        OtherResultA: Into<InternalResult<Response<Body>>> + Send,
+    {
+        self.nest("", router)
+    }
+
+    /// Mount every route of another [Router] under a `prefix` and return the new [Router]
+    ///
+    /// The `prefix` can have parameters, that are extracted with [`Path`](crate::handler::Path)
+    /// together with the ones from the nested routes:
+    ///
+    /// ```rust
+    /// # use yahf::handler::Path;
+    /// # use yahf::router::Router;
+    /// async fn list_users(Path(org): Path<String>) -> String
+    /// # { org }
+    /// async fn get_user(Path((org, id)): Path<(String, u64)>) -> String
+    /// # { format!("{} - {}", org, id) }
+    /// #
+    /// let users = Router::new()
+    ///     .get("/users", list_users, &(), &String::with_capacity(0))
+    ///     .get("/users/{id}", get_user, &(), &String::with_capacity(0));
+    ///
+    /// // Answers `/orgs/{org}/users` and `/orgs/{org}/users/{id}`
+    /// let router = Router::new().nest("/orgs/{org}", users);
+    /// ```
+    ///
+    /// Just like [`router`](Router::router), the middlewares of this [Router] are applied to
+    /// the nested routes, while the middlewares of the nested [Router] stay scoped to its own
+    /// routes
+    pub fn nest<OtherPreM, OtherAfterM, OtherFutA, OtherFutP, OtherResultP, OtherResultA>(
         mut self,
+        prefix: &'static str,
         router: Router<OtherPreM, OtherAfterM>,
     ) -> Router<PreM, AfterM>
     where
@@ -231,8 +283,8 @@ where
         OtherAfterM: AfterMiddleware<FutCallResponse = OtherFutA> + 'static,
         OtherFutP: Future<Output = OtherResultP> + Send,
         OtherFutA: Future<Output = OtherResultA> + Send,
-        OtherResultP: Into<InternalResult<Request<String>>> + Send,
-        OtherResultA: Into<InternalResult<Response<String>>> + Send,
+        OtherResultP: Into<InternalResult<Request<Body>>> + Send,
+        OtherResultA: Into<InternalResult<Response<Body>>> + Send,
     {
         let [get, put, delete, post, trace, options, connect, patch, head] = [
             router.get,
@@ -246,25 +298,95 @@ where
             router.head,
         ]
         .map(|handler| {
+            let handler = if router.states.is_empty() {
+                handler
+            } else {
+                handler.map(&|handler| router.states.wrap(handler))
+            };
+
             handler.apply(
                 self.middleware_factory
                     .clone(),
             )
         });
 
-        self.get.extend(get);
-        self.put.extend(put);
-        self.delete.extend(delete);
-        self.post.extend(post);
-        self.trace.extend(trace);
-        self.options.extend(options);
-        self.connect.extend(connect);
-        self.patch.extend(patch);
-        self.head.extend(head);
+        let middleware_factory = self
+            .middleware_factory
+            .clone();
+        self.fallbacks
+            .nest(prefix, router.fallbacks, &|handler| {
+                let handler = if router.states.is_empty() {
+                    handler
+                } else {
+                    router.states.wrap(handler)
+                };
+                let built = middleware_factory
+                    .clone()
+                    .build(handler, &(), &());
+
+                Box::new(encapsulate_runner(built, &(), &())) as BoxedHandler
+            });
+
+        self.get.nest(prefix, get);
+        self.put.nest(prefix, put);
+        self.delete
+            .nest(prefix, delete);
+        self.post.nest(prefix, post);
+        self.trace.nest(prefix, trace);
+        self.options
+            .nest(prefix, options);
+        self.connect
+            .nest(prefix, connect);
+        self.patch.nest(prefix, patch);
+        self.head.nest(prefix, head);
+
+        self.required_states
+            .nest(prefix, router.required_states, &router.states);
 
         self
     }
 
+    /// Attach a `state` to the [Router], making it available to its
+    /// [`handlers`](crate::handler::Runner) and [`middlewares`](crate::middleware) through the
+    /// [`State`](crate::handler::State) extractor
+    ///
+    /// The state is attached to every route of the [Router], including the ones bound after
+    /// calling this function and the ones from the routers merged with
+    /// [`router`](Router::router). When extended into another [Router], the routes keep seeing
+    /// their own states, while the outer [Router] routes and middlewares don't:
+    ///
+    /// ```rust
+    /// # use yahf::handler::State;
+    /// # use yahf::router::Router;
+    /// #[derive(Clone)]
+    /// struct Database;
+    ///
+    /// #[derive(Clone)]
+    /// struct Config { name: String }
+    ///
+    /// async fn users(State(_db): State<Database>, State(config): State<Config>) -> String
+    /// # { config.name }
+    ///
+    /// let users_router = Router::new()
+    ///     .with_state(Database)
+    ///     .get("/users", users, &(), &String::with_capacity(0));
+    ///
+    /// // `/users` can extract both `Database` and `Config`
+    /// let router = Router::new()
+    ///     .with_state(Config { name: "yahf".into() })
+    ///     .router(users_router);
+    /// ```
+    ///
+    /// When more than one state of the same type is attached to a route, the one closest to the
+    /// route wins
+    pub fn with_state<S>(mut self, state: S) -> Self
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        self.states.insert(state);
+        self
+    }
+
     /// Append a [`PreMiddleware`] on the
     /// [`PreMiddleware`] and return the [Router]
     pub fn pre<NewPreM, NewFut, NewResultP>(
@@ -274,13 +396,18 @@ where
     where
         NewPreM: PreMiddleware<FutCallResponse = NewFut>,
         NewFut: Future<Output = NewResultP>,
-        NewResultP: Into<InternalResult<Request<String>>>,
+        NewResultP: Into<InternalResult<Request<Body>>>,
     {
         let new_factory = self
             .middleware_factory
+            .as_ref()
+            .clone()
             .pre(middleware);
         Router {
             middleware_factory: Arc::new(new_factory),
+            states: self.states,
+            required_states: self.required_states,
+            fallbacks: self.fallbacks,
             get: self.get,
             put: self.put,
             delete: self.delete,
@@ -302,13 +429,18 @@ where
     where
         NewAfterM: AfterMiddleware<FutCallResponse = NewFut>,
         NewFut: Future<Output = NewResultA>,
-        NewResultA: Into<InternalResult<Response<String>>>,
+        NewResultA: Into<InternalResult<Response<Body>>>,
     {
         let new_factory = self
             .middleware_factory
+            .as_ref()
+            .clone()
             .after(middleware);
         Router {
             middleware_factory: Arc::new(new_factory),
+            states: self.states,
+            required_states: self.required_states,
+            fallbacks: self.fallbacks,
             get: self.get,
             put: self.put,
             delete: self.delete,
@@ -321,6 +453,40 @@ where
         }
     }
 
+    /// Append an [`AroundMiddleware`] and return the [Router]
+    ///
+    /// [`AroundMiddlewares`](AroundMiddleware) run after every [`PreMiddleware`] and before
+    /// every [`AfterMiddleware`], in the order they are appended
+    pub fn around<NewAroundM, NewFut, NewResult>(mut self, middleware: NewAroundM) -> Self
+    where
+        NewAroundM: AroundMiddleware<FutCallResponse = NewFut>,
+        NewFut: Future<Output = NewResult> + Send + 'static,
+        NewResult: Into<InternalResult<Response<Body>>>,
+    {
+        self.middleware_factory = Arc::new(
+            self.middleware_factory
+                .as_ref()
+                .clone()
+                .around(middleware),
+        );
+        self
+    }
+
+    fn tree_mut(&mut self, method: &Method) -> Option<&mut RouterTree<'static>> {
+        match *method {
+            Method::GET => Some(&mut self.get),
+            Method::PUT => Some(&mut self.put),
+            Method::DELETE => Some(&mut self.delete),
+            Method::POST => Some(&mut self.post),
+            Method::TRACE => Some(&mut self.trace),
+            Method::OPTIONS => Some(&mut self.options),
+            Method::CONNECT => Some(&mut self.connect),
+            Method::PATCH => Some(&mut self.patch),
+            Method::HEAD => Some(&mut self.head),
+            _ => None,
+        }
+    }
+
     /// Bind a [`handler`](crate::handler::Runner) to a [`HTTP method`](crate::request::Method) and a `path`, with a
     /// [`Serializer`](crate::serializer::BodySerializer) and
     /// [`Deserializer`](crate::deserializer::BodyDeserializer)
@@ -350,46 +516,17 @@ where
         Deserializer: 'static,
         Serializer: 'static,
     {
-        match method {
-            Method::GET => self.get.insert(
-                path,
-                Box::new(encapsulate_runner(handler, deserializer, serializer)),
-            ),
-            Method::PUT => self.put.insert(
-                path,
-                Box::new(encapsulate_runner(handler, deserializer, serializer)),
-            ),
-            Method::DELETE => self.delete.insert(
-                path,
-                Box::new(encapsulate_runner(handler, deserializer, serializer)),
-            ),
-            Method::POST => self.post.insert(
-                path,
-                Box::new(encapsulate_runner(handler, deserializer, serializer)),
-            ),
-            Method::TRACE => self.trace.insert(
-                path,
-                Box::new(encapsulate_runner(handler, deserializer, serializer)),
-            ),
-            Method::OPTIONS => self.options.insert(
-                path,
-                Box::new(encapsulate_runner(handler, deserializer, serializer)),
-            ),
-            Method::CONNECT => self.connect.insert(
-                path,
-                Box::new(encapsulate_runner(handler, deserializer, serializer)),
-            ),
-            Method::PATCH => self.patch.insert(
-                path,
-                Box::new(encapsulate_runner(handler, deserializer, serializer)),
-            ),
-            Method::HEAD => self.head.insert(
-                path,
-                Box::new(encapsulate_runner(handler, deserializer, serializer)),
-            ),
-            _ => unreachable!("HTTP methods allowed: GET, POST, PUT, DELETE, TRACE, OPTIONS, CONNECT, PATCH, HEAD"),
-        };
+        let handler: BoxedHandler = Box::new(encapsulate_runner(handler, deserializer, serializer));
+        self.tree_mut(&method)
+            .expect("HTTP methods allowed: GET, POST, PUT, DELETE, TRACE, OPTIONS, CONNECT, PATCH, HEAD")
+            .insert(path, handler);
+
+        self.require_states(method, path, R::required_states())
+    }
 
+    fn require_states(mut self, method: Method, path: &str, states: Vec<RequiredState>) -> Self {
+        self.required_states
+            .require(method.as_str(), path, states);
         self
     }
 
@@ -487,23 +624,535 @@ where
         router.head(path, handler, deserializer, serializer)
     }
 
+    /// Bind a [Route] to a `path`, with the middlewares that only apply to it
+    ///
+    /// The middlewares of the [Route] run inside the ones of the [Router]:
+    ///
+    /// ```rust
+    /// # use yahf::body::Body;
+    /// # use yahf::request::Request;
+    /// # use yahf::result::Result;
+    /// # use yahf::router::{Route, Router};
+    /// async fn log(req: Result<Request<Body>>) -> Result<Request<Body>>
+    /// # { req }
+    /// async fn authenticate(req: Result<Request<Body>>) -> Result<Request<Body>>
+    /// # { req }
+    /// async fn home() -> String
+    /// # { String::new() }
+    /// async fn admin() -> String
+    /// # { String::new() }
+    ///
+    /// // `/admin` runs `log -> authenticate -> admin`, while `/` runs `log -> home`
+    /// let router = Router::new()
+    ///     .pre(log)
+    ///     .get("/", home, &(), &String::with_capacity(0))
+    ///     .route(
+    ///         "/admin",
+    ///         Route::new()
+    ///             .get(admin, &(), &String::with_capacity(0))
+    ///             .with_pre(authenticate),
+    ///     );
+    /// ```
+    pub fn route(mut self, path: &'static str, mut route: Route) -> Self {
+        let body_limit = route.body_limit;
+        for (method, states) in std::mem::take(&mut route.required_states) {
+            self.required_states
+                .require(method.as_str(), path, states);
+        }
+        for (method, handler) in route.build() {
+            let built_with_middleware = self
+                .middleware_factory
+                .clone()
+                .build(handler, &(), &());
+
+            self.tree_mut(&method)
+                .expect("HTTP methods allowed: GET, POST, PUT, DELETE, TRACE, OPTIONS, CONNECT, PATCH, HEAD")
+                .insert_with_body_limit(
+                    path,
+                    Box::new(encapsulate_runner(built_with_middleware, &(), &())),
+                    body_limit,
+                );
+        }
+
+        self
+    }
+
+    /// Bind a [`handler`](crate::handler::Runner) to every request that doesn't match a route,
+    /// with a [`Serializer`](crate::serializer::BodySerializer) and
+    /// [`Deserializer`](crate::deserializer::BodyDeserializer)
+    ///
+    /// The fallback runs inside the [`middlewares`](crate::middleware) of the [Router], just
+    /// like any other route, so it can be used to answer a custom `404`:
+    ///
+    /// ```rust
+    /// # use serde::Serialize;
+    /// # use yahf::handler::Json;
+    /// # use yahf::request::Request;
+    /// # use yahf::response::Response;
+    /// # use yahf::router::Router;
+    /// #[derive(Serialize)]
+    /// struct NotFound { path: String }
+    ///
+    /// async fn not_found(req: Request<String>) -> Response<NotFound> {
+    ///     Response::builder()
+    ///         .status(404)
+    ///         .body(NotFound { path: req.uri().path().to_string() })
+    ///         .unwrap()
+    ///         .into()
+    /// }
+    ///
+    /// let router = Router::new().fallback(not_found, &String::with_capacity(0), &Json::new());
+    /// ```
+    ///
+    /// When the [Router] is nested under a `prefix`, its fallback only answers the requests
+    /// starting with that `prefix`, and the most specific fallback wins. Requests to a known path
+    /// with an unregistered [Method] are still answered with `405 Method Not Allowed`, through the
+    /// middlewares of the [Server](crate::server::Server)
+    pub fn fallback<FnIn, FnOut, Deserializer, Serializer, R>(
+        mut self,
+        handler: R,
+        deserializer: &Deserializer,
+        serializer: &Serializer,
+    ) -> Self
+    where
+        R: 'static + Runner<(FnIn, Deserializer), (FnOut, Serializer)>,
+        FnIn: 'static,
+        FnOut: 'static,
+        Deserializer: 'static,
+        Serializer: 'static,
+    {
+        let built_with_middleware = self
+            .middleware_factory
+            .clone()
+            .build(handler, deserializer, serializer);
+
+        self.fallbacks
+            .set(Box::new(encapsulate_runner(
+                built_with_middleware,
+                &(),
+                &(),
+            )));
+        self.required_states
+            .require("fallback", "", R::required_states());
+
+        self
+    }
+
+    pub(crate) fn states(&self) -> &States {
+        &self.states
+    }
+
+    /// Wrap a handler of the [Server](crate::server::Server) itself, like the ones answering
+    /// `405 Method Not Allowed` or `404 Not Found` without a fallback, with the middlewares of
+    /// the [Router]
+    pub(crate) fn with_middlewares<FnIn, FnOut, Deserializer, Serializer, R>(
+        &self,
+        handler: R,
+        deserializer: &Deserializer,
+        serializer: &Serializer,
+    ) -> BoxedHandler
+    where
+        R: 'static + Runner<(FnIn, Deserializer), (FnOut, Serializer)>,
+        FnIn: 'static,
+        FnOut: 'static,
+        Deserializer: 'static,
+        Serializer: 'static,
+    {
+        let built_with_middleware = self
+            .middleware_factory
+            .clone()
+            .build(handler, deserializer, serializer);
+
+        Box::new(encapsulate_runner(built_with_middleware, &(), &()))
+    }
+
+    /// Panic when a route requires a [State](crate::handler::State) that was never attached
+    pub(crate) fn check_states(&self) {
+        self.required_states
+            .check(&self.states);
+    }
+
+    pub(crate) fn find_fallback(&self, path: &str) -> Option<(RefHandler<'_>, PathParams)> {
+        self.fallbacks.get(path)
+    }
+
+    /// Every [Method] that can answer the `path`
+    ///
+    /// Besides the registered ones, `HEAD` is answered by the `GET` route and `OPTIONS` is
+    /// answered automatically
+    pub(crate) fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        let registered = [
+            (Method::GET, &self.get),
+            (Method::HEAD, &self.head),
+            (Method::POST, &self.post),
+            (Method::PUT, &self.put),
+            (Method::DELETE, &self.delete),
+            (Method::CONNECT, &self.connect),
+            (Method::OPTIONS, &self.options),
+            (Method::TRACE, &self.trace),
+            (Method::PATCH, &self.patch),
+        ]
+        .map(|(method, tree)| (method, tree.get(path).is_some()));
+
+        let has_get = registered[0].1;
+        let has_any = registered
+            .iter()
+            .any(|(_, is_registered)| *is_registered);
+
+        registered
+            .into_iter()
+            .filter(|(method, is_registered)| match *method {
+                Method::HEAD => *is_registered || has_get,
+                Method::OPTIONS => has_any,
+                _ => *is_registered,
+            })
+            .map(|(method, _)| method)
+            .collect()
+    }
+
+    /// Body limit of the route, when it replaces the one of the [Server](crate::server::Server)
+    pub(crate) fn find_body_limit(&self, method: &Method, path: &str) -> Option<BodyLimit> {
+        self.tree(method)
+            .and_then(|tree| tree.body_limit(path))
+    }
+
     #[allow(dead_code)]
-    pub(crate) fn find_route(&self, method: &Method, path: &str) -> Option<RefHandler<'_>> {
+    pub(crate) fn find_route(
+        &self,
+        method: &Method,
+        path: &str,
+    ) -> Option<(RefHandler<'_>, PathParams)> {
+        self.tree(method)
+            .and_then(|tree| tree.get(path))
+    }
+
+    fn tree(&self, method: &Method) -> Option<&RouterTree<'static>> {
         match *method {
-            Method::GET => self.get.get(path),
-            Method::PUT => self.put.get(path),
-            Method::POST => self.post.get(path),
-            Method::DELETE => self.delete.get(path),
-            Method::TRACE => self.trace.get(path),
-            Method::OPTIONS => self.options.get(path),
-            Method::CONNECT => self.connect.get(path),
-            Method::PATCH => self.patch.get(path),
-            Method::HEAD => self.head.get(path),
+            Method::GET => Some(&self.get),
+            Method::PUT => Some(&self.put),
+            Method::DELETE => Some(&self.delete),
+            Method::POST => Some(&self.post),
+            Method::TRACE => Some(&self.trace),
+            Method::OPTIONS => Some(&self.options),
+            Method::CONNECT => Some(&self.connect),
+            Method::PATCH => Some(&self.patch),
+            Method::HEAD => Some(&self.head),
             _ => None,
         }
     }
 }
 
+/// [`Handlers`](crate::handler::Runner) of a single path, together with the
+/// [`middlewares`](crate::middleware) that only apply to them
+///
+/// A [Route] is bound to a path with [`Router::route`] or
+/// [`Server::route`](crate::server::Server::route). Its middlewares wrap the handler of every
+/// [Method] of the [Route]. Whatever the order they are appended in, the
+/// [`PreMiddlewares`](PreMiddleware) run first, then the
+/// [`AroundMiddlewares`](crate::middleware::AroundMiddleware), and the
+/// [`AfterMiddlewares`](AfterMiddleware) last, each kind in the order it's appended:
+///
+/// ```rust
+/// # use yahf::body::Body;
+/// # use yahf::request::Request;
+/// # use yahf::result::Result;
+/// # use yahf::router::Route;
+/// async fn authenticate(req: Result<Request<Body>>) -> Result<Request<Body>>
+/// # { req }
+/// async fn rate_limit(req: Result<Request<Body>>) -> Result<Request<Body>>
+/// # { req }
+/// async fn read(body: String) -> String
+/// # { body }
+/// async fn write(body: String) -> String
+/// # { body }
+///
+/// // Both `GET` and `PUT` run `authenticate -> rate_limit -> handler`
+/// let route = Route::new()
+///     .get(read, &String::with_capacity(0), &String::with_capacity(0))
+///     .put(write, &String::with_capacity(0), &String::with_capacity(0))
+///     .with_pre(authenticate)
+///     .with_pre(rate_limit);
+/// ```
+#[derive(Default)]
+pub struct Route {
+    handlers: Vec<(Method, BoxedHandler)>,
+    required_states: Vec<(Method, Vec<RequiredState>)>,
+    body_limit: Option<BodyLimit>,
+    /// Middlewares of the route, in the order they are appended
+    pres: Vec<Layer>,
+    arounds: Vec<Layer>,
+    afters: Vec<Layer>,
+}
+
+macro_rules! route_method_insert {
+    ($fn: ident, $method: expr, $method_ref: literal, $method_name: literal) => {
+        #[doc = std::concat!("Bind a [`handler`](crate::handler::Runner) to a ",$method_ref, ", with a")]
+        /// [`Serializer`](crate::serializer::BodySerializer) and
+        /// [`Deserializer`](crate::deserializer::BodyDeserializer)
+        ///
+        /// ```rust
+        /// # use yahf::router::Route;
+        /// # async fn some_handler(req: String) -> String { req }
+        /// # let serializer = String::with_capacity(0);
+        /// # let deserializer = String::with_capacity(0);
+        /// # let route = Route::new();
+        #[doc = std::concat!( "route.", $method_name, "(some_handler, &deserializer, &serializer);")]
+        /// ```
+        pub fn $fn<FnIn, FnOut, Deserializer, Serializer, R>(
+            self,
+            handler: R,
+            deserializer: &Deserializer,
+            serializer: &Serializer,
+        ) -> Self
+        where
+            R: 'static + Runner<(FnIn, Deserializer), (FnOut, Serializer)>,
+            FnIn: 'static,
+            FnOut: 'static,
+            Deserializer: 'static,
+            Serializer: 'static,
+        {
+            self.method($method, handler, deserializer, serializer)
+        }
+    };
+}
+
+impl Route {
+    /// Create a new [Route], without any handler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind a [`handler`](crate::handler::Runner) to a [`HTTP method`](crate::request::Method), with a
+    /// [`Serializer`](crate::serializer::BodySerializer) and
+    /// [`Deserializer`](crate::deserializer::BodyDeserializer)
+    ///
+    /// ```rust
+    /// # use yahf::router::Route;
+    /// # use yahf::request::Method;
+    /// # async fn some_handler(req: String) -> String { req }
+    /// # let serializer = String::with_capacity(0);
+    /// # let deserializer = String::with_capacity(0);
+    /// # let route = Route::new();
+    /// route.method(Method::GET, some_handler, &deserializer, &serializer);
+    /// ```
+    pub fn method<FnIn, FnOut, Deserializer, Serializer, R>(
+        mut self,
+        method: Method,
+        handler: R,
+        deserializer: &Deserializer,
+        serializer: &Serializer,
+    ) -> Self
+    where
+        R: 'static + Runner<(FnIn, Deserializer), (FnOut, Serializer)>,
+        FnIn: 'static,
+        FnOut: 'static,
+        Deserializer: 'static,
+        Serializer: 'static,
+    {
+        self.handlers.push((
+            method.clone(),
+            Box::new(encapsulate_runner(handler, deserializer, serializer)),
+        ));
+        self.required_states
+            .push((method, R::required_states()));
+        self
+    }
+
+    route_method_insert!(
+        get,
+        Method::GET,
+        "[`GET Method`](crate::request::Method::GET)",
+        "get"
+    );
+    route_method_insert!(
+        put,
+        Method::PUT,
+        "[`PUT Method`](crate::request::Method::PUT)",
+        "put"
+    );
+    route_method_insert!(
+        delete,
+        Method::DELETE,
+        "[`DELETE Method`](crate::request::Method::DELETE)",
+        "delete"
+    );
+    route_method_insert!(
+        post,
+        Method::POST,
+        "[`POST Method`](crate::request::Method::POST)",
+        "post"
+    );
+    route_method_insert!(
+        trace,
+        Method::TRACE,
+        "[`TRACE Method`](crate::request::Method::TRACE)",
+        "trace"
+    );
+    route_method_insert!(
+        options,
+        Method::OPTIONS,
+        "[`OPTIONS Method`](crate::request::Method::OPTIONS)",
+        "options"
+    );
+    route_method_insert!(
+        connect,
+        Method::CONNECT,
+        "[`CONNECT Method`](crate::request::Method::CONNECT)",
+        "connect"
+    );
+    route_method_insert!(
+        patch,
+        Method::PATCH,
+        "[`PATCH Method`](crate::request::Method::PATCH)",
+        "patch"
+    );
+    route_method_insert!(
+        head,
+        Method::HEAD,
+        "[`HEAD Method`](crate::request::Method::HEAD)",
+        "head"
+    );
+
+    /// Bind a [`handler`](crate::handler::Runner) to every [`HTTP method`](crate::request::Method), with a
+    /// [`Serializer`](crate::serializer::BodySerializer) and
+    /// [`Deserializer`](crate::deserializer::BodyDeserializer)
+    ///
+    /// ```rust
+    /// # use yahf::router::Route;
+    /// # async fn some_handler(req: String) -> String { req }
+    /// # let serializer = String::with_capacity(0);
+    /// # let deserializer = String::with_capacity(0);
+    /// # let route = Route::new();
+    /// route.all(some_handler, &deserializer, &serializer);
+    /// ```
+    pub fn all<FnIn, FnOut, Deserializer, Serializer, R>(
+        self,
+        handler: R,
+        deserializer: &Deserializer,
+        serializer: &Serializer,
+    ) -> Self
+    where
+        R: 'static + Runner<(FnIn, Deserializer), (FnOut, Serializer)>,
+        FnIn: 'static,
+        FnOut: 'static,
+        Deserializer: 'static,
+        Serializer: 'static,
+    {
+        let route = self.get(handler.clone(), deserializer, serializer);
+        let route = route.put(handler.clone(), deserializer, serializer);
+        let route = route.delete(handler.clone(), deserializer, serializer);
+        let route = route.post(handler.clone(), deserializer, serializer);
+        let route = route.trace(handler.clone(), deserializer, serializer);
+        let route = route.options(handler.clone(), deserializer, serializer);
+        let route = route.connect(handler.clone(), deserializer, serializer);
+        let route = route.patch(handler.clone(), deserializer, serializer);
+
+        route.head(handler, deserializer, serializer)
+    }
+
+    /// Replace the body limit set by the [Limits](crate::limits::Limits) of the
+    /// [Server](crate::server::Server) for every handler of the [Route]
+    ///
+    /// Refeer to the [BodyLimit] for more information
+    pub fn body_limit(mut self, body_limit: BodyLimit) -> Self {
+        self.body_limit = Some(body_limit);
+        self
+    }
+
+    /// Append a [`PreMiddleware`] to every handler of the [Route]
+    ///
+    /// The first one appended runs first
+    pub fn with_pre<NewPreM, NewFut, NewResultP>(mut self, middleware: NewPreM) -> Self
+    where
+        NewPreM: PreMiddleware<FutCallResponse = NewFut> + 'static,
+        NewFut: Future<Output = NewResultP> + Send + 'static,
+        NewResultP: Into<InternalResult<Request<Body>>> + Send + 'static,
+    {
+        self.pres
+            .push(Self::layer(MiddlewareFactory::new().pre(middleware)));
+        self
+    }
+
+    /// Append an [`AfterMiddleware`] to every handler of the [Route]
+    ///
+    /// The first one appended runs first
+    pub fn with_after<NewAfterM, NewFut, NewResultA>(mut self, middleware: NewAfterM) -> Self
+    where
+        NewAfterM: AfterMiddleware<FutCallResponse = NewFut> + 'static,
+        NewFut: Future<Output = NewResultA> + Send + 'static,
+        NewResultA: Into<InternalResult<Response<Body>>> + Send + 'static,
+    {
+        self.afters
+            .push(Self::layer(MiddlewareFactory::new().after(middleware)));
+        self
+    }
+
+    /// Append an [`AroundMiddleware`] to every handler of the [Route]
+    ///
+    /// The first one appended runs first, after the [`PreMiddlewares`](PreMiddleware) and
+    /// before the [`AfterMiddlewares`](AfterMiddleware) of the [Route], even the ones appended
+    /// later
+    pub fn with_around<NewAroundM, NewFut, NewResult>(mut self, middleware: NewAroundM) -> Self
+    where
+        NewAroundM: AroundMiddleware<FutCallResponse = NewFut>,
+        NewFut: Future<Output = NewResult> + Send + 'static,
+        NewResult: Into<InternalResult<Response<Body>>>,
+    {
+        self.arounds
+            .push(Self::layer(MiddlewareFactory::new().around(middleware)));
+        self
+    }
+
+    /// Wrap a handler with the `middleware_factory`
+    fn layer<PreM, FutP, ResultP, AfterM, FutA, ResultA>(
+        middleware_factory: MiddlewareFactory<PreM, AfterM>,
+    ) -> Layer
+    where
+        PreM: PreMiddleware<FutCallResponse = FutP> + 'static,
+        FutP: Future<Output = ResultP> + Send + 'static,
+        ResultP: Into<InternalResult<Request<Body>>> + Send + 'static,
+        AfterM: AfterMiddleware<FutCallResponse = FutA> + 'static,
+        FutA: Future<Output = ResultA> + Send + 'static,
+        ResultA: Into<InternalResult<Response<Body>>> + Send + 'static,
+    {
+        let middleware_factory = Arc::new(middleware_factory);
+        Arc::new(move |handler| {
+            let built = middleware_factory
+                .clone()
+                .build(handler, &(), &());
+
+            Box::new(encapsulate_runner(built, &(), &()))
+        })
+    }
+
+    /// Every handler of the [Route], wrapped with its middlewares
+    ///
+    /// The first [`PreMiddleware`] or [`AroundMiddleware`] appended must run first, so it has to
+    /// be the outermost one of its kind, while the first [`AfterMiddleware`] appended must be the
+    /// innermost one. The [`AfterMiddlewares`](AfterMiddleware) wrap the
+    /// [`AroundMiddlewares`](AroundMiddleware), so they see the [Response] they return, and the
+    /// [`PreMiddlewares`](PreMiddleware) wrap everything else
+    fn build(self) -> impl Iterator<Item = (Method, BoxedHandler)> {
+        let layers: Vec<Layer> = self
+            .arounds
+            .into_iter()
+            .rev()
+            .chain(self.afters)
+            .chain(self.pres.into_iter().rev())
+            .collect();
+
+        self.handlers
+            .into_iter()
+            .map(move |(method, handler)| {
+                let handler = layers
+                    .iter()
+                    .fold(handler, |handler, layer| layer(handler));
+
+                (method, handler)
+            })
+    }
+}
+
 #[cfg(test)]
 mod test {
     mod runners {
@@ -558,27 +1207,28 @@ mod test {
 
     mod utils {
         use crate::{
+            body::Body,
             handler::RefHandler,
             request::{Method, Request},
             response::Response,
             result::InternalResult,
         };
 
-        pub fn create_request(body: String, method: Method) -> Request<String> {
+        pub fn create_request(body: String, method: Method) -> Request<Body> {
             Request::builder()
                 .method(method)
                 .header("Content-Length", body.len())
                 .body(body)
         }
 
-        pub fn test_runner_response(body: String, expected_body: String) {
-            assert!(body == expected_body);
+        pub fn test_runner_response(body: Body, expected_body: &str) {
+            assert!(body.as_bytes().unwrap() == expected_body);
         }
 
         pub async fn run_runner(
             runner: RefHandler<'_>,
-            request: InternalResult<Request<String>>,
-        ) -> InternalResult<Response<String>> {
+            request: InternalResult<Request<Body>>,
+        ) -> InternalResult<Response<Body>> {
             runner.call(request).await
         }
     }
@@ -619,11 +1269,11 @@ mod test {
 
                 assert!(handler.is_some());
 
-                let response = super::utils::run_runner(handler.unwrap(), request.into()).await;
+                let response = super::utils::run_runner(handler.unwrap().0, request.into()).await;
 
                 super::utils::test_runner_response(
-                    response.map_or_else(|err| err.into(), |res| res).body().to_owned(),
-                    $expected_body.to_owned(),
+                    response.map_or_else(|err| err.body().clone().into(), |res| res.into_body()),
+                    $expected_body,
                 );
 
                 Ok(())
@@ -781,41 +1431,40 @@ mod test {
     );
 
     mod middlewares {
+        use crate::body::Body;
         use crate::error::Error;
         use crate::request::Request;
         use crate::response::Response;
         use crate::result::Result;
 
-        pub async fn pre_transform(req: Result<Request<String>>) -> Result<Request<String>> {
+        pub async fn pre_transform(req: Result<Request<Body>>) -> Result<Request<Body>> {
             req.into_inner()
                 .map(|_| Request::new("PM1".into()))
                 .into()
         }
 
-        pub async fn pre_generate_error(_req: Result<Request<String>>) -> Result<Request<String>> {
+        pub async fn pre_generate_error(_req: Result<Request<Body>>) -> Result<Request<Body>> {
             Err(Error::new("PM2".into(), 500)).into()
         }
 
-        pub async fn pre_handle_error(req: Result<Request<String>>) -> Result<Request<String>> {
+        pub async fn pre_handle_error(req: Result<Request<Body>>) -> Result<Request<Body>> {
             Ok(req
                 .into_inner()
                 .unwrap_or(Request::new("PM3".into())))
             .into()
         }
 
-        pub async fn after_transform(res: Result<Response<String>>) -> Result<Response<String>> {
+        pub async fn after_transform(res: Result<Response<Body>>) -> Result<Response<Body>> {
             res.into_inner()
                 .map(|_| Response::new("AM1".into()))
                 .into()
         }
 
-        pub async fn after_generate_error(
-            _res: Result<Response<String>>,
-        ) -> Result<Response<String>> {
+        pub async fn after_generate_error(_res: Result<Response<Body>>) -> Result<Response<Body>> {
             Err(Error::new("AM2".into(), 500)).into()
         }
 
-        pub async fn after_handle_error(res: Result<Response<String>>) -> Result<Response<String>> {
+        pub async fn after_handle_error(res: Result<Response<Body>>) -> Result<Response<Body>> {
             Ok(res
                 .into_inner()
                 .unwrap_or(Response::new("AM3".into())))
@@ -1,15 +1,32 @@
+use std::any::TypeId;
+
+use futures::Future;
 use serde::de::DeserializeOwned;
 
 use crate::{
-    deserializer::BodyDeserializer, handler::StandardBodyType, request::Request,
+    body::Body,
+    deserializer::{check_content_type, BodyDeserializer},
+    error::Error,
+    handler::{Path, Query, StandardBodyType, State},
+    multipart::Multipart,
+    path::PathParams,
+    request::{HeaderMap, Method, Parts, Request, Uri},
     result::InternalResult,
 };
 
 /// Describes a type that can be extracted using a BodyExtractors
 pub trait RunnerInput<Extractor> {
-    fn try_into(input: InternalResult<Request<StandardBodyType>>) -> InternalResult<Self>
+    fn try_into(
+        input: InternalResult<Request<StandardBodyType>>,
+    ) -> impl Future<Output = InternalResult<Self>> + Send
     where
         Self: std::marker::Sized;
+
+    /// State that must be attached to every route of a handler taking `Self`, checked when the
+    /// [Server](crate::server::Server) starts
+    fn required_state() -> Option<RequiredState> {
+        None
+    }
 }
 
 impl<BodyType, Extractor> RunnerInput<Extractor> for BodyType
@@ -17,11 +34,18 @@ where
     Extractor: BodyDeserializer<Item = BodyType>,
     BodyType: DeserializeOwned,
 {
-    fn try_into(input: InternalResult<Request<String>>) -> InternalResult<Self>
+    #[allow(clippy::manual_async_fn)]
+    fn try_into(
+        input: InternalResult<Request<StandardBodyType>>,
+    ) -> impl Future<Output = InternalResult<Self>> + Send
     where
         Self: std::marker::Sized,
     {
-        input.and_then(|input| Extractor::deserialize(input.body()))
+        async move {
+            let (parts, body) = input?.into_parts();
+            check_content_type::<Extractor>(&parts.headers, &body)?;
+            Extractor::deserialize(body).await
+        }
     }
 }
 
@@ -30,24 +54,282 @@ where
     Extractor: BodyDeserializer<Item = BodyType>,
     BodyType: DeserializeOwned,
 {
-    fn try_into(input: InternalResult<Request<String>>) -> InternalResult<Self>
+    #[allow(clippy::manual_async_fn)]
+    fn try_into(
+        input: InternalResult<Request<StandardBodyType>>,
+    ) -> impl Future<Output = InternalResult<Self>> + Send
     where
         Self: std::marker::Sized,
     {
-        input.and_then(|input| input.and_then(|body| Extractor::deserialize(&body)))
+        async move {
+            let (parts, body) = input?.into_parts();
+            check_content_type::<Extractor>(&parts.headers, &body)?;
+            Extractor::deserialize(body)
+                .await
+                .map(|body| Request::from_parts(parts, body))
+        }
     }
 }
 
-impl<BodyType, Extractor, RInput> RunnerInput<Extractor> for crate::result::Result<RInput>
+impl<Extractor> RunnerInput<Extractor> for Request<Body> {
+    #[allow(clippy::manual_async_fn)]
+    fn try_into(
+        input: InternalResult<Request<StandardBodyType>>,
+    ) -> impl Future<Output = InternalResult<Self>> + Send
+    where
+        Self: std::marker::Sized,
+    {
+        async move { input }
+    }
+}
+
+impl<Extractor> RunnerInput<Extractor> for Body {
+    #[allow(clippy::manual_async_fn)]
+    fn try_into(
+        input: InternalResult<Request<StandardBodyType>>,
+    ) -> impl Future<Output = InternalResult<Self>> + Send
+    where
+        Self: std::marker::Sized,
+    {
+        async move { input.map(Request::into_body) }
+    }
+}
+
+impl<Extractor> RunnerInput<Extractor> for Multipart {
+    #[allow(clippy::manual_async_fn)]
+    fn try_into(
+        input: InternalResult<Request<StandardBodyType>>,
+    ) -> impl Future<Output = InternalResult<Self>> + Send
+    where
+        Self: std::marker::Sized,
+    {
+        async move {
+            let (parts, body) = input?.into_parts();
+            Multipart::from_parts(&parts, body)
+        }
+    }
+}
+
+impl<Extractor, RInput> RunnerInput<Extractor> for crate::result::Result<RInput>
 where
-    Extractor: BodyDeserializer<Item = BodyType>,
-    BodyType: DeserializeOwned,
     RInput: RunnerInput<Extractor>,
 {
-    fn try_into(input: InternalResult<Request<String>>) -> InternalResult<Self>
+    #[allow(clippy::manual_async_fn)]
+    fn try_into(
+        input: InternalResult<Request<StandardBodyType>>,
+    ) -> impl Future<Output = InternalResult<Self>> + Send
+    where
+        Self: std::marker::Sized,
+    {
+        async move {
+            Ok(RInput::try_into(input)
+                .await
+                .into())
+        }
+    }
+}
+
+macro_rules! impl_runner_input_from_parts {
+    ($($extractor: ident),+) => {
+        $(
+            impl<T, Extractor> RunnerInput<Extractor> for $extractor<T>
+            where
+                $extractor<T>: RunnerInputParts,
+            {
+                #[allow(clippy::manual_async_fn)]
+                fn try_into(
+                    input: InternalResult<Request<StandardBodyType>>,
+                ) -> impl Future<Output = InternalResult<Self>> + Send
+                where
+                    Self: std::marker::Sized,
+                {
+                    async move { Self::try_from_parts(&input?.into_parts().0) }
+                }
+
+                fn required_state() -> Option<RequiredState> {
+                    <Self as RunnerInputParts>::required_state()
+                }
+            }
+        )+
+    };
+}
+
+impl_runner_input_from_parts!(Path, Query, State);
+
+/// The parts of the request can also be the last argument of a handler bound with the `()`
+/// deserializer, that ignores the body
+macro_rules! impl_runner_input_ignoring_body {
+    ($($extractor: ident),+) => {
+        $(
+            impl RunnerInput<()> for $extractor {
+                #[allow(clippy::manual_async_fn)]
+                fn try_into(
+                    input: InternalResult<Request<StandardBodyType>>,
+                ) -> impl Future<Output = InternalResult<Self>> + Send
+                where
+                    Self: std::marker::Sized,
+                {
+                    async move { Self::try_from_parts(&input?.into_parts().0) }
+                }
+            }
+        )+
+    };
+}
+
+impl_runner_input_ignoring_body!(Method, Uri, HeaderMap);
+
+/// Describes a type that can be extracted from the [Parts] of a request, without consuming the
+/// body
+///
+/// Every argument of a handler, except the last one, must implement this trait. The last argument
+/// is the only one with access to the body and must implement [RunnerInput]. [Method], [Uri] and
+/// [HeaderMap] implement both, so they can also be the last argument of a handler bound with the
+/// `()` deserializer.
+pub trait RunnerInputParts {
+    /// Extract `Self` from the [Parts] of the request, failing with the
+    /// [Error](crate::error::Error) the request is answered with
+    fn try_from_parts(parts: &Parts) -> InternalResult<Self>
+    where
+        Self: std::marker::Sized;
+
+    /// State that must be attached to every route of a handler taking `Self`, checked when the
+    /// [Server](crate::server::Server) starts
+    fn required_state() -> Option<RequiredState> {
+        None
+    }
+}
+
+/// State a handler argument can't be extracted without, like the one of a
+/// [State](crate::handler::State)
+///
+/// A [Server](crate::server::Server) refuses to start when a route lacks a state one of its
+/// arguments requires, instead of answering every request to it with a `500 Internal Server
+/// Error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequiredState {
+    id: TypeId,
+    name: &'static str,
+}
+
+impl RequiredState {
+    /// Require the state of type `S`
+    pub fn of<S>() -> Self
+    where
+        S: 'static,
+    {
+        Self {
+            id: TypeId::of::<S>(),
+            name: std::any::type_name::<S>(),
+        }
+    }
+
+    pub(crate) fn id(&self) -> TypeId {
+        self.id
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> RunnerInputParts for Path<T>
+where
+    T: DeserializeOwned,
+{
+    fn try_from_parts(parts: &Parts) -> InternalResult<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        parts
+            .extensions
+            .get::<PathParams>()
+            .map_or_else(
+                || PathParams::default().deserialize(),
+                PathParams::deserialize,
+            )
+            .map(Path)
+    }
+}
+
+impl<T> RunnerInputParts for Query<T>
+where
+    T: DeserializeOwned,
+{
+    fn try_from_parts(parts: &Parts) -> InternalResult<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        serde_urlencoded::from_str(
+            parts
+                .uri
+                .query()
+                .unwrap_or_default(),
+        )
+        .map(Query)
+        .map_err(|err| Error::new(format!("Invalid query string: {}", err), 400))
+    }
+}
+
+impl<S> RunnerInputParts for State<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn try_from_parts(parts: &Parts) -> InternalResult<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or_else(|| {
+                Error::new(
+                    format!("State of type {} not found", std::any::type_name::<S>()),
+                    500,
+                )
+            })
+    }
+
+    fn required_state() -> Option<RequiredState> {
+        Some(RequiredState::of::<S>())
+    }
+}
+
+impl RunnerInputParts for Method {
+    fn try_from_parts(parts: &Parts) -> InternalResult<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        Ok(parts.method.clone())
+    }
+}
+
+impl RunnerInputParts for Uri {
+    fn try_from_parts(parts: &Parts) -> InternalResult<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        Ok(parts.uri.clone())
+    }
+}
+
+impl RunnerInputParts for HeaderMap {
+    fn try_from_parts(parts: &Parts) -> InternalResult<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        Ok(parts.headers.clone())
+    }
+}
+
+impl<RInput> RunnerInputParts for crate::result::Result<RInput>
+where
+    RInput: RunnerInputParts,
+{
+    fn try_from_parts(parts: &Parts) -> InternalResult<Self>
     where
         Self: std::marker::Sized,
     {
-        Ok(RInput::try_into(input).into())
+        Ok(RInput::try_from_parts(parts).into())
     }
 }
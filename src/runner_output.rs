@@ -1,9 +1,48 @@
 use serde::Serialize;
 
-use crate::{response::Response, result::InternalResult, serializer::BodySerializer};
+use crate::{
+    body::Body,
+    handler::StandardBodyType,
+    response::{IntoResponse, Response},
+    result::InternalResult,
+    serializer::BodySerializer,
+};
 
-pub trait RunnerOutput<Serializer> {
-    fn try_into(self) -> InternalResult<Response<String>>;
+/// Describes how to convert the value returned by a handler into a [Response], using the
+/// `Serializer` of the route
+///
+/// The `accept` is the `Accept` header of the request, used by serializers that
+/// [negotiate](crate::handler::Negotiate) the format of the body
+///
+/// The `Marker` only tells apart implementations that would otherwise overlap, like the ones
+/// for any serializable type and for the types implementing [IntoResponse]
+pub trait RunnerOutput<Serializer, Marker = ()> {
+    fn try_into(self, accept: Option<&str>) -> InternalResult<Response<StandardBodyType>>;
+}
+
+/// Marker of the [RunnerOutput] implemented for the types implementing [IntoResponse]
+pub struct ViaIntoResponse;
+
+/// Set the `Content-Type` chosen by the serializer, unless there's one already, and the `Vary`
+/// of a serializer that negotiates the format
+fn with_content_type<Serializer>(
+    mut response: Response<StandardBodyType>,
+    media_type: Option<&'static str>,
+) -> Response<StandardBodyType>
+where
+    Serializer: BodySerializer,
+{
+    if let Some(media_type) = media_type {
+        response
+            .headers_mut()
+            .entry(http::header::CONTENT_TYPE)
+            .or_insert_with(|| http::HeaderValue::from_static(media_type));
+    }
+    if Serializer::varies_on_accept() {
+        crate::accept::vary(response.headers_mut());
+    }
+
+    response
 }
 
 impl<BodyType, Serializer> RunnerOutput<Serializer> for Response<BodyType>
@@ -11,8 +50,12 @@ where
     Serializer: BodySerializer<Item = BodyType>,
     BodyType: Serialize,
 {
-    fn try_into(self) -> InternalResult<Response<String>> {
-        self.and_then(|body| Serializer::serialize(body))
+    fn try_into(self, accept: Option<&str>) -> InternalResult<Response<StandardBodyType>> {
+        let (parts, body) = self.into_parts();
+
+        Serializer::serialize_accepting(body, accept).map(|(body, media_type)| {
+            with_content_type::<Serializer>(Response::from_parts(parts, body), media_type)
+        })
     }
 }
 
@@ -21,20 +64,42 @@ where
     Serializer: BodySerializer<Item = BodyType>,
     BodyType: Serialize,
 {
-    fn try_into(self) -> InternalResult<Response<String>> {
-        Serializer::serialize(self).map(Response::new)
+    fn try_into(self, accept: Option<&str>) -> InternalResult<Response<StandardBodyType>> {
+        Serializer::serialize_accepting(self, accept).map(|(body, media_type)| {
+            with_content_type::<Serializer>(Response::new(body), media_type)
+        })
+    }
+}
+
+impl<Serializer> RunnerOutput<Serializer> for Response<Body> {
+    fn try_into(self, _accept: Option<&str>) -> InternalResult<Response<StandardBodyType>> {
+        Ok(self)
+    }
+}
+
+impl<Serializer> RunnerOutput<Serializer> for Body {
+    fn try_into(self, _accept: Option<&str>) -> InternalResult<Response<StandardBodyType>> {
+        Ok(Response::new(self))
+    }
+}
+
+impl<Serializer, T> RunnerOutput<Serializer, ViaIntoResponse> for T
+where
+    T: IntoResponse,
+    Response<T::Body>: RunnerOutput<Serializer>,
+{
+    fn try_into(self, accept: Option<&str>) -> InternalResult<Response<StandardBodyType>> {
+        RunnerOutput::<Serializer>::try_into(self.into_response(), accept)
     }
 }
 
-impl<BodyType, Serializer, BasicRunnerOutput> RunnerOutput<Serializer>
+impl<Serializer, Marker, BasicRunnerOutput> RunnerOutput<Serializer, Marker>
     for crate::result::Result<BasicRunnerOutput>
 where
-    Serializer: BodySerializer<Item = BodyType>,
-    BodyType: Serialize,
-    BasicRunnerOutput: RunnerOutput<Serializer>,
+    BasicRunnerOutput: RunnerOutput<Serializer, Marker>,
 {
-    fn try_into(self) -> InternalResult<Response<String>> {
+    fn try_into(self, accept: Option<&str>) -> InternalResult<Response<StandardBodyType>> {
         self.into_inner()
-            .and_then(|resp| BasicRunnerOutput::try_into(resp))
+            .and_then(|resp| BasicRunnerOutput::try_into(resp, accept))
     }
 }
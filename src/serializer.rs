@@ -1,15 +1,48 @@
+use bytes::Bytes;
 use serde::Serialize;
 
 use crate::{
+    accept,
     error::Error,
-    handler::{Json, StandardBodyType},
+    handler::{Form, Json, Negotiate, StandardBodyType},
     result::InternalResult,
 };
 
+/// Describes how to convert the `Item` returned by the handler into a
+/// [Body](crate::body::Body)
+///
+/// A serializer can either produce the whole [Body](crate::body::Body) at once or
+/// [stream](crate::body::Body::from_stream) it
+///
+/// The media type declared by the serializer is sent as the `Content-Type` of the response,
+/// unless the handler already set one
 pub trait BodySerializer {
     type Item;
 
     fn serialize(content: Self::Item) -> InternalResult<StandardBodyType>;
+
+    /// Media type of the serialized [Body](crate::body::Body)
+    fn media_type() -> Option<&'static str> {
+        None
+    }
+
+    /// Serialize the `Item` for a request with the `accept` header, returning the
+    /// [Body](crate::body::Body) along with its media type
+    ///
+    /// Only serializers that [negotiate](crate::handler::Negotiate) the format need to look at
+    /// the `accept` header, the others just [serialize](BodySerializer::serialize)
+    fn serialize_accepting(
+        content: Self::Item,
+        _accept: Option<&str>,
+    ) -> InternalResult<(StandardBodyType, Option<&'static str>)> {
+        Self::serialize(content).map(|body| (body, Self::media_type()))
+    }
+
+    /// Whether the [Body](crate::body::Body) depends on the `accept` header, so the response
+    /// is sent with a `Vary: Accept`
+    fn varies_on_accept() -> bool {
+        false
+    }
 }
 
 impl<T> BodySerializer for Json<T>
@@ -18,16 +51,198 @@ where
 {
     type Item = T;
 
-    fn serialize(content: Self::Item) -> InternalResult<String> {
-        serde_json::to_string(&content).map_err(|err| Error::new(err.to_string(), 422))
+    fn serialize(content: Self::Item) -> InternalResult<StandardBodyType> {
+        serde_json::to_vec(&content)
+            .map(StandardBodyType::from)
+            .map_err(|err| Error::new(err.to_string(), 422))
+    }
+
+    fn media_type() -> Option<&'static str> {
+        Some("application/json")
+    }
+}
+
+impl<T> BodySerializer for Form<T>
+where
+    T: Serialize,
+{
+    type Item = T;
+
+    fn serialize(content: Self::Item) -> InternalResult<StandardBodyType> {
+        serde_urlencoded::to_string(&content)
+            .map(StandardBodyType::from)
+            .map_err(|err| Error::new(err.to_string(), 422))
+    }
+
+    fn media_type() -> Option<&'static str> {
+        Some("application/x-www-form-urlencoded")
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl<T> BodySerializer for crate::handler::MsgPack<T>
+where
+    T: Serialize,
+{
+    type Item = T;
+
+    fn serialize(content: Self::Item) -> InternalResult<StandardBodyType> {
+        rmp_serde::to_vec_named(&content)
+            .map(StandardBodyType::from)
+            .map_err(|err| Error::new(err.to_string(), 422))
+    }
+
+    fn media_type() -> Option<&'static str> {
+        Some("application/msgpack")
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl<T> BodySerializer for crate::handler::Cbor<T>
+where
+    T: Serialize,
+{
+    type Item = T;
+
+    fn serialize(content: Self::Item) -> InternalResult<StandardBodyType> {
+        let mut body = Vec::new();
+        ciborium::into_writer(&content, &mut body)
+            .map(|_| StandardBodyType::from(body))
+            .map_err(|err| Error::new(err.to_string(), 422))
+    }
+
+    fn media_type() -> Option<&'static str> {
+        Some("application/cbor")
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl<T> BodySerializer for crate::handler::Yaml<T>
+where
+    T: Serialize,
+{
+    type Item = T;
+
+    fn serialize(content: Self::Item) -> InternalResult<StandardBodyType> {
+        serde_norway::to_string(&content)
+            .map(StandardBodyType::from)
+            .map_err(|err| Error::new(err.to_string(), 422))
+    }
+
+    fn media_type() -> Option<&'static str> {
+        Some("application/yaml")
+    }
+}
+
+#[cfg(feature = "toml")]
+impl<T> BodySerializer for crate::handler::Toml<T>
+where
+    T: Serialize,
+{
+    type Item = T;
+
+    fn serialize(content: Self::Item) -> InternalResult<StandardBodyType> {
+        toml::to_string(&content)
+            .map(StandardBodyType::from)
+            .map_err(|err| Error::new(err.to_string(), 422))
+    }
+
+    fn media_type() -> Option<&'static str> {
+        Some("application/toml")
     }
 }
 
+/// Serializer of one of the formats offered by a [Negotiate]
+type SerializeFn<Item> = fn(Item) -> InternalResult<(StandardBodyType, Option<&'static str>)>;
+
+macro_rules! impl_negotiate_for_tuple {
+    ($first: ident $(, $serializers: ident)*) => {
+        impl<$first, $($serializers,)*> BodySerializer for Negotiate<($first, $($serializers,)*)>
+        where
+            $first: BodySerializer,
+            $($serializers: BodySerializer<Item = <$first as BodySerializer>::Item>,)*
+        {
+            type Item = <$first as BodySerializer>::Item;
+
+            fn serialize(content: Self::Item) -> InternalResult<StandardBodyType> {
+                $first::serialize(content)
+            }
+
+            fn media_type() -> Option<&'static str> {
+                $first::media_type()
+            }
+
+            fn serialize_accepting(
+                content: Self::Item,
+                accept: Option<&str>,
+            ) -> InternalResult<(StandardBodyType, Option<&'static str>)> {
+                let offers: &[(Option<&'static str>, SerializeFn<Self::Item>)] = &[
+                    ($first::media_type(), |content| {
+                        $first::serialize(content).map(|body| (body, $first::media_type()))
+                    }),
+                    $(($serializers::media_type(), |content| {
+                        $serializers::serialize(content).map(|body| (body, $serializers::media_type()))
+                    }),)*
+                ];
+                // Without a media type, a serializer can't be matched against the `accept` header
+                let (media_types, serializers): (Vec<&str>, Vec<&SerializeFn<Self::Item>>) = offers
+                    .iter()
+                    .filter_map(|(media_type, serializer)| media_type.map(|media_type| (media_type, serializer)))
+                    .unzip();
+
+                match accept::preferred(accept, &media_types) {
+                    Some(index) => serializers[index](content),
+                    None => Err(not_acceptable(&media_types)),
+                }
+            }
+
+            fn varies_on_accept() -> bool {
+                true
+            }
+        }
+    };
+}
+
+impl_negotiate_for_tuple!(S1);
+impl_negotiate_for_tuple!(S1, S2);
+impl_negotiate_for_tuple!(S1, S2, S3);
+impl_negotiate_for_tuple!(S1, S2, S3, S4);
+impl_negotiate_for_tuple!(S1, S2, S3, S4, S5);
+impl_negotiate_for_tuple!(S1, S2, S3, S4, S5, S6);
+
+/// Error for a [Negotiate] where none of the `media_types` is acceptable
+fn not_acceptable(media_types: &[&str]) -> Error {
+    Error::new(
+        format!(
+            "Not Acceptable, expected one of: {}",
+            media_types.join(", ")
+        ),
+        406,
+    )
+    .with_error_code("not_acceptable")
+}
+
 impl BodySerializer for String {
     type Item = String;
 
     fn serialize(content: Self::Item) -> InternalResult<StandardBodyType> {
-        Ok(content.to_owned())
+        Ok(content.into())
+    }
+
+    fn media_type() -> Option<&'static str> {
+        Some("text/plain; charset=utf-8")
+    }
+}
+
+impl BodySerializer for Bytes {
+    type Item = Bytes;
+
+    fn serialize(content: Self::Item) -> InternalResult<StandardBodyType> {
+        Ok(content.into())
+    }
+
+    fn media_type() -> Option<&'static str> {
+        Some("application/octet-stream")
     }
 }
 
@@ -35,6 +250,6 @@ impl BodySerializer for () {
     type Item = ();
 
     fn serialize(_content: Self::Item) -> InternalResult<StandardBodyType> {
-        Ok(String::with_capacity(0))
+        Ok(StandardBodyType::empty())
     }
 }
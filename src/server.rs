@@ -6,23 +6,34 @@ use std::{
     convert::Infallible,
     ops::{Deref, DerefMut},
     sync::Arc,
+    time::Duration,
 };
 
-use tokio_rustls::rustls::ServerConfig;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{oneshot, watch},
+};
+use tokio_rustls::rustls::ServerConfig as RustlsConfig;
 
 use crate::{
-    handler::Runner,
-    middleware::{AfterMiddleware, PreMiddleware},
+    body::Body,
+    connection::{self, Connection, Connections, InFlightBody, Incoming},
+    error::{ErrorRenderer, ErrorRenderers},
+    handler::{BoxedHandler, Runner},
+    limits::Limits,
+    middleware::{AfterMiddleware, AroundMiddleware, PreMiddleware},
+    path::PathParams,
     request::{self, Request},
     response::Response,
     result::InternalResult,
-    router::Router,
+    router::{Route, Router},
 };
 
 use futures::Future;
 use http::StatusCode;
 use hyper::{
-    server::conn::{AddrIncoming, AddrStream},
+    rt::Executor,
+    server::{accept::Accept, conn::AddrIncoming},
     service::{make_service_fn, service_fn},
 };
 
@@ -53,16 +64,194 @@ use request::Method;
 /// ```
 pub struct Server<PreM, AfterM> {
     router: Router<PreM, AfterM>,
+    shutdown_timeout: Option<Duration>,
+    error_renderers: ErrorRenderers,
+    limits: Limits,
+    config: ServerConfig,
+}
+
+/// Outcome of a graceful shutdown, returned by [`Server::listen_with_shutdown`] and
+/// [`Server::listen_rustls_with_shutdown`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shutdown {
+    /// Every connection finished before the [`shutdown timeout`](Server::shutdown_timeout)
+    Graceful,
+    /// The [`shutdown timeout`](Server::shutdown_timeout) was reached and the connections that
+    /// were still open got closed
+    TimedOut {
+        /// Number of connections that were still open
+        open_connections: usize,
+    },
+}
+
+/// Configuration of the connections accepted by a [Server], used by both
+/// [`listen`](Server::listen) and [`listen_rustls`](Server::listen_rustls)
+///
+/// ```rust
+/// use std::time::Duration;
+/// use yahf::server::{Server, ServerConfig};
+///
+/// let server = Server::new().config(
+///     ServerConfig::new()
+///         .header_read_timeout(Duration::from_secs(5))
+///         .request_timeout(Duration::from_secs(30))
+///         .keep_alive_timeout(Duration::from_secs(60))
+///         .tcp_nodelay(true)
+///         .http1_only()
+///         .max_connections(10_000),
+/// );
+/// ```
+///
+/// Whatever isn't set keeps the defaults of [hyper](https://docs.rs/hyper/0.14): no timeouts,
+/// keep-alive on, and both HTTP/1 and HTTP/2, either with prior knowledge (h2c) or negotiated
+/// through TLS
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    header_read_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    keep_alive: bool,
+    keep_alive_timeout: Option<Duration>,
+    tcp_nodelay: bool,
+    protocol: Protocol,
+    http2_stream_window_size: Option<u32>,
+    http2_connection_window_size: Option<u32>,
+    http2_adaptive_window: bool,
+    http2_max_concurrent_streams: Option<u32>,
+    max_connections: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Any,
+    Http1,
+    Http2,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            header_read_timeout: None,
+            request_timeout: None,
+            read_timeout: None,
+            keep_alive: true,
+            keep_alive_timeout: None,
+            tcp_nodelay: false,
+            protocol: Protocol::Any,
+            http2_stream_window_size: None,
+            http2_connection_window_size: None,
+            http2_adaptive_window: false,
+            http2_max_concurrent_streams: None,
+            max_connections: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Create the default [ServerConfig]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Close the HTTP/1 connections that take longer than the `timeout` to send the headers of
+    /// a request
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.header_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Answer with a `408 Request Timeout` when the body of a request takes longer than the
+    /// `timeout` to be read, counting from the moment its headers were received
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Answer with a `408 Request Timeout` when the client takes longer than the `timeout` to
+    /// send the next chunk of the body
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep the HTTP/1 connections open between requests, on by default
+    pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Close the HTTP/1 connections that stay idle, without any request in flight, for longer
+    /// than the `timeout`
+    ///
+    /// HTTP/2 connections aren't closed when idle, since dropping their socket would skip the
+    /// `GOAWAY` the client expects. They are pinged every `timeout` instead, and closed when a
+    /// ping isn't acknowledged within the `timeout`
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Set `TCP_NODELAY` on the accepted connections
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.tcp_nodelay = nodelay;
+        self
+    }
+
+    /// Only accept HTTP/1, refusing HTTP/2 with prior knowledge (h2c) and only offering HTTP/1.1
+    /// through TLS
+    pub fn http1_only(mut self) -> Self {
+        self.protocol = Protocol::Http1;
+        self
+    }
+
+    /// Only accept HTTP/2, either with prior knowledge (h2c) or negotiated through TLS
+    pub fn http2_only(mut self) -> Self {
+        self.protocol = Protocol::Http2;
+        self
+    }
+
+    /// Set the initial window size of the HTTP/2 streams, in bytes
+    pub fn http2_initial_stream_window_size(mut self, size: u32) -> Self {
+        self.http2_stream_window_size = Some(size);
+        self
+    }
+
+    /// Set the initial window size of the HTTP/2 connections, in bytes
+    pub fn http2_initial_connection_window_size(mut self, size: u32) -> Self {
+        self.http2_connection_window_size = Some(size);
+        self
+    }
+
+    /// Adapt the HTTP/2 windows to the bandwidth of the connection, overriding the initial
+    /// window sizes
+    pub fn http2_adaptive_window(mut self, adaptive: bool) -> Self {
+        self.http2_adaptive_window = adaptive;
+        self
+    }
+
+    /// Limit the number of concurrent streams of a HTTP/2 connection
+    pub fn http2_max_concurrent_streams(mut self, max: u32) -> Self {
+        self.http2_max_concurrent_streams = Some(max);
+        self
+    }
+
+    /// Stop accepting connections while there are `max` connections open
+    ///
+    /// The connections over the limit wait in the backlog of the listener
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
 }
 
 impl<PreM, FutP, ResultP, AfterM, FutA, ResultA> Deref for Server<PreM, AfterM>
 where
     PreM: PreMiddleware<FutCallResponse = FutP>,
     FutP: Future<Output = ResultP>,
-    ResultP: Into<InternalResult<Request<String>>>,
+    ResultP: Into<InternalResult<Request<Body>>>,
     AfterM: AfterMiddleware<FutCallResponse = FutA>,
     FutA: Future<Output = ResultA>,
-    ResultA: Into<InternalResult<Response<String>>>,
+    ResultA: Into<InternalResult<Response<Body>>>,
 {
     type Target = Router<PreM, AfterM>;
 
@@ -75,10 +264,10 @@ impl<PreM, FutP, ResultP, AfterM, FutA, ResultA> DerefMut for Server<PreM, After
 where
     PreM: PreMiddleware<FutCallResponse = FutP>,
     FutP: Future<Output = ResultP>,
-    ResultP: Into<InternalResult<Request<String>>>,
+    ResultP: Into<InternalResult<Request<Body>>>,
     AfterM: AfterMiddleware<FutCallResponse = FutA>,
     FutA: Future<Output = ResultA>,
-    ResultA: Into<InternalResult<Response<String>>>,
+    ResultA: Into<InternalResult<Response<Body>>>,
 {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.router
@@ -89,14 +278,18 @@ impl Server<(), ()> {
     /// Create a new [Server]
     pub fn new() -> Server<
         impl PreMiddleware<
-            FutCallResponse = impl Future<Output = impl Into<InternalResult<Request<String>>>>,
+            FutCallResponse = impl Future<Output = impl Into<InternalResult<Request<Body>>>>,
         >,
         impl AfterMiddleware<
-            FutCallResponse = impl Future<Output = impl Into<InternalResult<Response<String>>>>,
+            FutCallResponse = impl Future<Output = impl Into<InternalResult<Response<Body>>>>,
         >,
     > {
         Server {
             router: Router::new(),
+            shutdown_timeout: None,
+            error_renderers: ErrorRenderers::default(),
+            limits: Limits::default(),
+            config: ServerConfig::default(),
         }
     }
 }
@@ -142,10 +335,10 @@ impl<PreM, FutP, ResultP, AfterM, FutA, ResultA> Server<PreM, AfterM>
 where
     PreM: PreMiddleware<FutCallResponse = FutP> + 'static,
     FutP: Future<Output = ResultP> + std::marker::Send + 'static,
-    ResultP: Into<InternalResult<Request<String>>> + std::marker::Send + 'static,
+    ResultP: Into<InternalResult<Request<Body>>> + std::marker::Send + 'static,
     AfterM: AfterMiddleware<FutCallResponse = FutA> + 'static,
     FutA: Future<Output = ResultA> + std::marker::Send + 'static,
-    ResultA: Into<InternalResult<Response<String>>> + std::marker::Send + 'static,
+    ResultA: Into<InternalResult<Response<Body>>> + std::marker::Send + 'static,
 {
     method_reroute!(get, "[`GET Method`](crate::request::Method::GET)", "get");
     method_reroute!(put, "[`PUT Method`](crate::request::Method::PUT)", "put");
@@ -221,11 +414,55 @@ where
         self
     }
 
+    /// Bind a [`handler`](crate::handler::Runner) to every request that doesn't match a route,
+    /// with a [`Serializer`](crate::serializer::BodySerializer) and
+    /// [`Deserializer`](crate::deserializer::BodyDeserializer)
+    ///
+    /// ```rust
+    /// # use yahf::request::Request;
+    /// # use yahf::response::Response;
+    /// # use yahf::server::Server;
+    /// async fn not_found(req: Request<String>) -> Response<String> {
+    ///     Response::builder()
+    ///         .status(404)
+    ///         .body(format!("{} not found", req.uri().path()))
+    ///         .unwrap()
+    ///         .into()
+    /// }
+    ///
+    /// let server = Server::new().fallback(
+    ///     not_found,
+    ///     &String::with_capacity(0),
+    ///     &String::with_capacity(0),
+    /// );
+    /// ```
+    ///
+    /// The fallback runs inside the global [`middlewares`](crate::middleware) set before it
+    pub fn fallback<FnIn, FnOut, Deserializer, Serializer, R>(
+        mut self,
+        handler: R,
+        deserializer: &Deserializer,
+        serializer: &Serializer,
+    ) -> Self
+    where
+        R: 'static + Runner<(FnIn, Deserializer), (FnOut, Serializer)>,
+        FnIn: 'static,
+        FnOut: 'static,
+        Deserializer: 'static,
+        Serializer: 'static,
+    {
+        let router = self.router;
+        let router = router.fallback(handler, deserializer, serializer);
+        self.router = router;
+        self
+    }
+
     /// Extend the [Server] with a [Router] and return the new [Server]
     ///
     /// A example:
     ///
     /// ```rust
+    /// # use yahf::body::Body;
     /// # use yahf::request::Request;
     /// # use yahf::router::Router;
     ///# use yahf::result::Result;
@@ -236,7 +473,7 @@ where
     /// # #[derive(Deserialize, Serialize)]
     /// # struct Computation { value: u64 }
     /// #
-    /// async fn logger(req: Result<Request<String>>) -> Result<Request<String>>
+    /// async fn logger(req: Result<Request<Body>>) -> Result<Request<Body>>
     /// # { req }
     /// #
     /// async fn some_computation(req: Computation) -> Computation
@@ -264,11 +501,83 @@ where
         OtherAfterM: AfterMiddleware<FutCallResponse = OtherFutA> + 'static,
         OtherFutP: Future<Output = OtherResultP> + Send,
         OtherFutA: Future<Output = OtherResultA> + Send,
-        OtherResultP: Into<InternalResult<Request<String>>> + Send,
-        OtherResultA: Into<InternalResult<Response<String>>> + Send,
+        OtherResultP: Into<InternalResult<Request<Body>>> + Send,
+        OtherResultA: Into<InternalResult<Response<Body>>> + Send,
     {
         let new_router = self.router.router(router);
-        Self { router: new_router }
+        Self {
+            router: new_router,
+            ..self
+        }
+    }
+
+    /// Mount every route of a [Router] under a `prefix` and return the new [Server]
+    ///
+    /// ```rust
+    /// # use yahf::handler::Path;
+    /// # use yahf::router::Router;
+    /// # use yahf::server::Server;
+    /// async fn get_user(Path((org, id)): Path<(String, u64)>) -> String
+    /// # { format!("{} - {}", org, id) }
+    /// #
+    /// let users = Router::new().get("/users/{id}", get_user, &(), &String::with_capacity(0));
+    ///
+    /// // Answers `/api/v1/orgs/{org}/users/{id}`
+    /// let server = Server::new().nest("/api/v1/orgs/{org}", users);
+    /// ```
+    ///
+    /// The middlewares of the [Server] are applied to the nested routes, while the middlewares of
+    /// the [Router] stay scoped to its own routes
+    pub fn nest<OtherPreM, OtherAfterM, OtherFutA, OtherFutP, OtherResultP, OtherResultA>(
+        self,
+        prefix: &'static str,
+        router: Router<OtherPreM, OtherAfterM>,
+    ) -> Self
+    where
+        OtherPreM: PreMiddleware<FutCallResponse = OtherFutP> + 'static,
+        OtherAfterM: AfterMiddleware<FutCallResponse = OtherFutA> + 'static,
+        OtherFutP: Future<Output = OtherResultP> + Send,
+        OtherFutA: Future<Output = OtherResultA> + Send,
+        OtherResultP: Into<InternalResult<Request<Body>>> + Send,
+        OtherResultA: Into<InternalResult<Response<Body>>> + Send,
+    {
+        let new_router = self
+            .router
+            .nest(prefix, router);
+        Self {
+            router: new_router,
+            ..self
+        }
+    }
+
+    /// Attach a `state` to the [Server], making it available to every
+    /// [`handler`](crate::handler::Runner) and [`middleware`](crate::middleware) through the
+    /// [`State`](crate::handler::State) extractor
+    ///
+    /// ```rust
+    /// # use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    /// # use yahf::handler::State;
+    /// # use yahf::server::Server;
+    /// #[derive(Clone, Default)]
+    /// struct Counter(Arc<AtomicUsize>);
+    ///
+    /// async fn visit(State(counter): State<Counter>) -> String {
+    ///     counter.0.fetch_add(1, Ordering::SeqCst).to_string()
+    /// }
+    ///
+    /// let server = Server::new()
+    ///     .with_state(Counter::default())
+    ///     .get("/", visit, &(), &String::with_capacity(0));
+    /// ```
+    ///
+    /// States attached to a [Router] with [`Router::with_state`] take precedence over the ones
+    /// attached to the [Server] on the routes of that [Router]
+    pub fn with_state<S>(self, state: S) -> Self
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let router = self.router.with_state(state);
+        Self { router, ..self }
     }
 
     /// Append a [`PreMiddleware`] on the
@@ -280,11 +589,17 @@ where
     where
         NewPreM: PreMiddleware<FutCallResponse = NewFut>,
         NewFut: Future<Output = NewResultP>,
-        NewResultP: Into<InternalResult<Request<String>>>,
+        NewResultP: Into<InternalResult<Request<Body>>>,
     {
         let new_router = self.router.pre(middleware);
 
-        Server { router: new_router }
+        Server {
+            router: new_router,
+            shutdown_timeout: self.shutdown_timeout,
+            error_renderers: self.error_renderers,
+            limits: self.limits,
+            config: self.config,
+        }
     }
 
     /// Append a [`AfterMiddleware`] on the
@@ -296,26 +611,173 @@ where
     where
         NewAfterM: AfterMiddleware<FutCallResponse = NewFut>,
         NewFut: Future<Output = NewResultA>,
-        NewResultA: Into<InternalResult<Response<String>>>,
+        NewResultA: Into<InternalResult<Response<Body>>>,
     {
         let new_router = self.router.after(middleware);
 
-        Server { router: new_router }
+        Server {
+            router: new_router,
+            shutdown_timeout: self.shutdown_timeout,
+            error_renderers: self.error_renderers,
+            limits: self.limits,
+            config: self.config,
+        }
+    }
+
+    /// Append an [`AroundMiddleware`] and return the [Server]
+    ///
+    /// Refeer to [`Router::around`] for more information
+    pub fn around<NewAroundM, NewFut, NewResult>(self, middleware: NewAroundM) -> Self
+    where
+        NewAroundM: AroundMiddleware<FutCallResponse = NewFut>,
+        NewFut: Future<Output = NewResult> + Send + 'static,
+        NewResult: Into<InternalResult<Response<Body>>>,
+    {
+        let router = self.router.around(middleware);
+        Self { router, ..self }
+    }
+
+    /// Bind a [Route] to a `path`, with the middlewares that only apply to it
+    ///
+    /// Refeer to [`Router::route`] for more information
+    pub fn route(self, path: &'static str, route: Route) -> Self {
+        let router = self.router.route(path, route);
+        Self { router, ..self }
+    }
+
+    /// Render every [Error](crate::error::Error) with the `renderer`, whatever the `Accept`
+    /// header of the request is
+    ///
+    /// By default, the [Error](crate::error::Error) is rendered as `text/plain`,
+    /// `application/json` or `application/problem+json`, following the `Accept` header, and
+    /// falls back to `text/plain`:
+    ///
+    /// ```rust
+    /// use yahf::error::ProblemJsonRenderer;
+    /// use yahf::server::Server;
+    ///
+    /// let server = Server::new().error_renderer(ProblemJsonRenderer);
+    /// ```
+    pub fn error_renderer(mut self, renderer: impl ErrorRenderer) -> Self {
+        self.error_renderers = ErrorRenderers::only(renderer);
+        self
+    }
+
+    /// Set the [ServerConfig] of the connections
+    pub fn config(mut self, config: ServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set the [Limits] of the body and the headers of every request
+    ///
+    /// By default, the body is limited to [DEFAULT_BODY_LIMIT](crate::limits::DEFAULT_BODY_LIMIT),
+    /// and the headers to [DEFAULT_HEADER_COUNT](crate::limits::DEFAULT_HEADER_COUNT) headers and
+    /// [DEFAULT_HEADER_SIZE](crate::limits::DEFAULT_HEADER_SIZE) bytes
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set how long a graceful shutdown waits for the open connections to finish, before
+    /// closing them
+    ///
+    /// Without a timeout, the [Server] waits for every connection to finish
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = Some(timeout);
+        self
     }
 
     /// Start listening for [Requests](crate::request::Request) on the
     /// [address](std::net::SocketAddr)
     pub async fn listen(self, addr: std::net::SocketAddr) -> Result<(), hyper::Error> {
-        let server = Arc::new(self);
-        let make_svc = make_service_fn(move |_: &AddrStream| {
-            let server = server.clone();
-            let service = service_fn(move |req| handle_req(server.clone(), req));
-            async move { Ok::<_, Infallible>(service) }
-        });
+        self.listen_with_shutdown(addr, futures::future::pending())
+            .await
+            .map(|_| ())
+    }
+
+    /// Start listening for [Requests](crate::request::Request) on the
+    /// [address](std::net::SocketAddr) until the `signal` completes
+    ///
+    /// Once the `signal` completes, the [Server] stops accepting new connections and waits for
+    /// the open ones to finish their requests, up to the [`shutdown
+    /// timeout`](Server::shutdown_timeout):
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use yahf::server::{Server, Shutdown};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let server = Server::new()
+    ///         .get("/", || async { "Hello world".to_string() }, &(), &String::with_capacity(0))
+    ///         .shutdown_timeout(Duration::from_secs(30));
+    ///
+    ///     let shutdown = server
+    ///         .listen_with_shutdown(([127, 0, 0, 1], 8000).into(), async {
+    ///             tokio::signal::ctrl_c().await.unwrap();
+    ///         })
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     if let Shutdown::TimedOut { open_connections } = shutdown {
+    ///         eprintln!("Closed {} connections before they finished", open_connections);
+    ///     }
+    /// }
+    /// ```
+    pub async fn listen_with_shutdown(
+        self,
+        addr: std::net::SocketAddr,
+        signal: impl Future<Output = ()>,
+    ) -> Result<Shutdown, hyper::Error> {
+        let mut incoming = AddrIncoming::bind(&addr)?;
+        incoming.set_nodelay(self.config.tcp_nodelay);
+
+        self.serve(incoming, signal)
+            .await
+    }
+
+    /// Start listening for [Requests](crate::request::Request) on an already bound
+    /// [TcpListener](tokio::net::TcpListener)
+    ///
+    /// Binding the listener beforehand gives access to its address, like the port picked by the
+    /// system when binding to port `0`:
+    ///
+    /// ```rust,no_run
+    /// use yahf::server::Server;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    ///     println!("Listening on {}", listener.local_addr().unwrap());
+    ///
+    ///     Server::new()
+    ///         .get("/", || async { "Hello world".to_string() }, &(), &String::with_capacity(0))
+    ///         .listen_on(listener)
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub async fn listen_on(self, listener: tokio::net::TcpListener) -> Result<(), hyper::Error> {
+        self.listen_on_with_shutdown(listener, futures::future::pending())
+            .await
+            .map(|_| ())
+    }
 
-        let server = hyper::Server::bind(&addr).serve(make_svc);
-        server.await?;
-        Ok(())
+    /// Start listening for [Requests](crate::request::Request) on an already bound
+    /// [TcpListener](tokio::net::TcpListener) until the `signal` completes
+    ///
+    /// The shutdown works just like in [`listen_with_shutdown`](Server::listen_with_shutdown)
+    pub async fn listen_on_with_shutdown(
+        self,
+        listener: tokio::net::TcpListener,
+        signal: impl Future<Output = ()>,
+    ) -> Result<Shutdown, hyper::Error> {
+        let mut incoming = AddrIncoming::from_listener(listener)?;
+        incoming.set_nodelay(self.config.tcp_nodelay);
+
+        self.serve(incoming, signal)
+            .await
     }
 
     /// Start securely listening for [Requests](crate::request::Request) on the
@@ -325,72 +787,310 @@ where
     /// [A example]( https://github.com/lucasduartesobreira/yahf/tree/main/examples/tls )
     pub async fn listen_rustls(
         self,
-        config: ServerConfig,
+        config: RustlsConfig,
         addr: std::net::SocketAddr,
     ) -> Result<(), hyper::Error> {
+        self.listen_rustls_with_shutdown(config, addr, futures::future::pending())
+            .await
+            .map(|_| ())
+    }
+
+    /// Start securely listening for [Requests](crate::request::Request) on the
+    /// [address](std::net::SocketAddr) using the [rustls
+    /// config](tokio_rustls::rustls::ServerConfig), until the `signal` completes
+    ///
+    /// The shutdown works just like in [`listen_with_shutdown`](Server::listen_with_shutdown)
+    pub async fn listen_rustls_with_shutdown(
+        self,
+        config: RustlsConfig,
+        addr: std::net::SocketAddr,
+        signal: impl Future<Output = ()>,
+    ) -> Result<Shutdown, hyper::Error> {
+        let mut addr_inc = AddrIncoming::bind(&addr)?;
+        addr_inc.set_nodelay(self.config.tcp_nodelay);
+
+        let acceptor = TlsAcceptor::builder().with_tls_config(config);
+        let acceptor = match self.config.protocol {
+            Protocol::Any => acceptor.with_all_versions_alpn(),
+            Protocol::Http1 => acceptor.with_http11_alpn(),
+            Protocol::Http2 => acceptor.with_http2_alpn(),
+        };
+        let listener = acceptor.with_incoming(addr_inc);
+
+        self.serve(listener, signal)
+            .await
+    }
+
+    async fn serve<I>(
+        self,
+        incoming: I,
+        signal: impl Future<Output = ()>,
+    ) -> Result<Shutdown, hyper::Error>
+    where
+        I: Accept,
+        I::Conn: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        I::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.check_states();
+
+        let shutdown_timeout = self.shutdown_timeout;
+        let config = self.config.clone();
+        let limits = self.limits;
         let server = Arc::new(self);
-        let make_svc = make_service_fn(move |_| {
+        let connections = Arc::new(Connections::default());
+        // Dropping the sender, when this function returns, closes the remaining connections
+        let (_close_connections, closed) = watch::channel(());
+
+        let idle_timeout = match config.protocol {
+            Protocol::Http2 => None,
+            Protocol::Any | Protocol::Http1 => config.keep_alive_timeout,
+        };
+        let incoming = Incoming::new(
+            incoming,
+            connections.clone(),
+            config.max_connections,
+            idle_timeout,
+        );
+        let make_svc = make_service_fn(move |conn: &Connection<I::Conn>| {
             let server = server.clone();
-            let service = service_fn(move |req| handle_req(server.clone(), req));
+            let requests = conn.requests();
+            let service = service_fn(move |req| {
+                let in_flight = requests.start();
+                let response = handle_req(server.clone(), req);
+                async move {
+                    response
+                        .await
+                        .map(|response| response.map(|body| InFlightBody::new(body, in_flight)))
+                }
+            });
             async move { Ok::<_, Infallible>(service) }
         });
-        let addr_inc = AddrIncoming::bind(&addr).unwrap();
 
-        let listener = TlsAcceptor::builder()
-            .with_tls_config(config)
-            .with_all_versions_alpn()
-            .with_incoming(addr_inc);
+        let (start_draining, draining) = oneshot::channel();
+        let mut builder = hyper::Server::builder(incoming)
+            .executor(ClosableExecutor(closed))
+            .http1_keepalive(config.keep_alive)
+            .http2_initial_stream_window_size(config.http2_stream_window_size)
+            .http2_initial_connection_window_size(config.http2_connection_window_size)
+            .http2_adaptive_window(config.http2_adaptive_window)
+            .http2_max_concurrent_streams(config.http2_max_concurrent_streams);
+        if let Some(timeout) = config.header_read_timeout {
+            builder = builder.http1_header_read_timeout(timeout);
+        }
+        if let Some(timeout) = config.keep_alive_timeout {
+            builder = builder
+                .http2_keep_alive_interval(timeout)
+                .http2_keep_alive_timeout(timeout);
+        }
+        if let Some(size) = limits.http1_max_buf_size() {
+            builder = builder.http1_max_buf_size(size);
+        }
+        if let Some(size) = limits.http2_max_header_list_size() {
+            builder = builder.http2_max_header_list_size(size);
+        }
+        builder = match config.protocol {
+            Protocol::Any => builder,
+            Protocol::Http1 => builder.http1_only(true),
+            Protocol::Http2 => builder.http2_only(true),
+        };
+
+        let server = builder
+            .serve(make_svc)
+            .with_graceful_shutdown(async move {
+                signal.await;
+                let _ = start_draining.send(());
+            });
+
+        let timeout = async move {
+            match (draining.await, shutdown_timeout) {
+                (Ok(()), Some(timeout)) => tokio::time::sleep(timeout).await,
+                _ => futures::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            result = server => result.map(|_| Shutdown::Graceful),
+            _ = timeout => Ok(Shutdown::TimedOut {
+                open_connections: connections.open(),
+            }),
+        }
+    }
+}
+
+/// Spawn the connections on tokio, stopping them once the [watch::Sender] is dropped
+#[derive(Clone)]
+struct ClosableExecutor(watch::Receiver<()>);
 
-        let server = hyper::Server::builder(listener).serve(make_svc);
-        server.await?;
-        Ok(())
+impl<F> Executor<F> for ClosableExecutor
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        let mut closed = self.0.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = fut => {},
+                _ = closed.changed() => {},
+            }
+        });
     }
 }
 
-async fn handle_req<PreM, FutP, ResultP, AfterM, FutA, ResultA>(
+pub(crate) async fn handle_req<PreM, FutP, ResultP, AfterM, FutA, ResultA, B>(
     server: Arc<Server<PreM, AfterM>>,
-    req: hyper::Request<hyper::Body>,
+    req: hyper::Request<B>,
 ) -> Result<hyper::Response<hyper::Body>, Box<dyn std::error::Error + Send + Sync>>
 where
+    B: Into<Body>,
     PreM: PreMiddleware<FutCallResponse = FutP> + 'static,
     FutP: Future<Output = ResultP> + std::marker::Send + 'static,
-    ResultP: Into<InternalResult<Request<String>>> + std::marker::Send + 'static,
+    ResultP: Into<InternalResult<Request<Body>>> + std::marker::Send + 'static,
     AfterM: AfterMiddleware<FutCallResponse = FutA> + 'static,
     FutA: Future<Output = ResultA> + std::marker::Send + 'static,
-    ResultA: Into<InternalResult<Response<String>>> + std::marker::Send + 'static,
+    ResultA: Into<InternalResult<Response<Body>>> + std::marker::Send + 'static,
 {
-    let handler = server.find_route(req.method(), req.uri().path());
+    let accept = req
+        .headers()
+        .get(http::header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(str::to_string);
 
-    let handler = match handler {
-        Some(handler) => handler,
+    if let Err(err) = server
+        .limits
+        .check_headers(req.headers())
+    {
+        return Ok(server
+            .error_renderers
+            .render(err, accept.as_deref())
+            .into_inner()
+            .map(hyper::Body::from));
+    }
+
+    let method = req.method().clone();
+    let route_method = match method {
+        Method::HEAD
+            if server
+                .find_route(&method, req.uri().path())
+                .is_none() =>
+        {
+            Method::GET
+        }
+        _ => method.clone(),
+    };
+    let handler = server.find_route(&route_method, req.uri().path());
+
+    // Answered by the server, but still through its middlewares
+    let unrouted: BoxedHandler;
+    let (handler, params) = match handler {
+        Some(found) => found,
         None => {
-            return Ok(hyper::Response::builder()
-                .status(StatusCode::NOT_FOUND)
-                .body(hyper::Body::empty())
-                .unwrap());
+            let allowed_methods = server.allowed_methods(req.uri().path());
+            if !allowed_methods.is_empty() {
+                unrouted = server.with_middlewares(
+                    move |req: Request<Body>| {
+                        let allowed_methods = allowed_methods.clone();
+                        async move { method_not_allowed(req.method(), allowed_methods) }
+                    },
+                    &(),
+                    &(),
+                );
+                (&*unrouted, PathParams::default())
+            } else {
+                match server.find_fallback(req.uri().path()) {
+                    Some(found) => found,
+                    None => {
+                        unrouted = server.with_middlewares(not_found, &(), &());
+                        (&*unrouted, PathParams::default())
+                    }
+                }
+            }
         }
     };
 
     let (parts, body) = req.into_parts();
-    let str = String::from_utf8(
-        hyper::body::to_bytes(body)
-            .await?
-            .to_vec(),
-    )?;
-    let req_new = hyper::Request::from_parts(parts, str);
-
-    let (parts, body) = handler
+    let body = connection::with_timeouts(
+        body.into(),
+        server.config.request_timeout,
+        server.config.read_timeout,
+    );
+    let body = match server.limits.limit_body(
+        &parts.headers,
+        body,
+        server.find_body_limit(&route_method, parts.uri.path()),
+    ) {
+        Ok(body) => body,
+        Err(err) => {
+            return Ok(server
+                .error_renderers
+                .render(err, accept.as_deref())
+                .into_inner()
+                .map(hyper::Body::from))
+        }
+    };
+    let mut req_new = hyper::Request::from_parts(parts, body);
+    server
+        .states()
+        .inject(req_new.extensions_mut());
+    req_new
+        .extensions_mut()
+        .insert(params);
+
+    let (mut parts, body) = handler
         .call(Ok(Request::from(req_new)))
         .await
-        .map_or_else(|err| err.into(), |res| res)
+        .unwrap_or_else(|err| {
+            server
+                .error_renderers
+                .render(err, accept.as_deref())
+        })
         .into_inner()
         .into_parts();
 
-    let body = hyper::Body::from(body);
+    let body = match method {
+        Method::HEAD => {
+            if let Some(bytes) = body.as_bytes() {
+                parts
+                    .headers
+                    .entry(http::header::CONTENT_LENGTH)
+                    .or_insert_with(|| bytes.len().into());
+            }
+
+            hyper::Body::empty()
+        }
+        _ => hyper::Body::from(body),
+    };
 
     Ok(hyper::Response::from_parts(parts, body))
 }
 
+async fn not_found() -> Response<Body> {
+    http::Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::empty())
+        .expect("A valid status")
+        .into()
+}
+
+fn method_not_allowed(method: &Method, allowed_methods: Vec<Method>) -> Response<Body> {
+    let allow = allowed_methods
+        .iter()
+        .map(Method::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let status = match *method {
+        Method::OPTIONS => StatusCode::NO_CONTENT,
+        _ => StatusCode::METHOD_NOT_ALLOWED,
+    };
+
+    http::Response::builder()
+        .status(status)
+        .header(http::header::ALLOW, allow)
+        .body(Body::empty())
+        .expect("A valid status and Allow header")
+        .into()
+}
+
 #[cfg(test)]
 mod test {
 
@@ -405,7 +1105,9 @@ mod test {
         request::{Method, Request},
         response::Response,
         result::InternalResult,
-        server::Server,
+        router::Route,
+        server::{Server, Shutdown},
+        testing::TestClient,
     };
 
     struct TestReq {
@@ -415,68 +1117,53 @@ mod test {
 
     async fn run_req<PreM, FutP, ResultP, AfterM, FutA, ResultA>(
         server: Server<PreM, AfterM>,
-        addr: SocketAddr,
         test_req: TestReq,
-    ) -> Result<(), hyper::Error>
-    where
+    ) where
         PreM: PreMiddleware<FutCallResponse = FutP> + 'static,
         FutP: Future<Output = ResultP> + std::marker::Send + 'static,
-        ResultP: Into<InternalResult<Request<String>>> + std::marker::Send + 'static,
+        ResultP: Into<InternalResult<Request<crate::body::Body>>> + std::marker::Send + 'static,
         AfterM: AfterMiddleware<FutCallResponse = FutA> + 'static,
         FutA: Future<Output = ResultA> + std::marker::Send + 'static,
-        ResultA: Into<InternalResult<Response<String>>> + std::marker::Send + 'static,
+        ResultA: Into<InternalResult<Response<crate::body::Body>>> + std::marker::Send + 'static,
     {
-        tokio::spawn(server.listen(addr));
-
         let TestReq {
-            mut req,
+            req,
             res: expected_res,
         } = test_req;
 
-        *req.uri_mut() = format!("http://localhost:{}/", addr.port())
-            .parse()
-            .unwrap();
-
-        let client = Client::new();
-        let response = client.request(req).await?;
+        let response = TestClient::new(server)
+            .send(req)
+            .await;
 
-        assert!(response.status() == expected_res.status());
-
-        let body_str = String::from_utf8(
-            hyper::body::to_bytes(response.into_body())
-                .await
-                .unwrap()
-                .to_vec(),
-        )
-        .unwrap();
-
-        assert!(body_str.as_str() == expected_res.into_body());
-
-        Ok(())
+        response.assert_status(expected_res.status());
+        expected_res
+            .headers()
+            .iter()
+            .for_each(|(name, value)| {
+                response.assert_header(name.as_str(), value.to_str().unwrap());
+            });
+        response.assert_text(expected_res.body());
     }
 
     macro_rules! test_with_server {
-        ($name: ident, $server: expr, $ip: literal, $req: expr, $res: expr) => {
+        ($name: ident, $server: expr, $req: expr, $res: expr) => {
             #[tokio::test]
             async fn $name() {
                 let server = $server;
-                let response = run_req(
+                run_req(
                     server,
-                    $ip.parse().unwrap(),
                     TestReq {
                         req: $req,
                         res: $res,
                     },
                 )
                 .await;
-
-                assert!(response.is_ok(), "{:?}", response);
             }
         };
     }
 
     macro_rules! test_server_method {
-        ($name: ident, $method: ident, $req: expr, $ip: literal) => {
+        ($name: ident, $method: ident, $req: expr) => {
             #[tokio::test]
             async fn $name() {
                 let server = Server::new().$method(
@@ -485,17 +1172,14 @@ mod test {
                     &(),
                     &String::with_capacity(0),
                 );
-                let response = run_req(
+                run_req(
                     server,
-                    $ip.parse().unwrap(),
                     TestReq {
                         req: $req,
                         res: hyper::Response::new("Hello world!"),
                     },
                 )
                 .await;
-
-                assert!(response.is_ok(), "{:?}", response);
             }
         };
     }
@@ -506,8 +1190,7 @@ mod test {
         hyper::Request::builder()
             .method(Method::GET)
             .body(Body::from(""))
-            .unwrap(),
-        "127.0.0.1:8000"
+            .unwrap()
     );
     test_server_method!(
         test_server_post,
@@ -515,8 +1198,7 @@ mod test {
         hyper::Request::builder()
             .method(Method::POST)
             .body(Body::from(""))
-            .unwrap(),
-        "127.0.0.1:8001"
+            .unwrap()
     );
     test_server_method!(
         test_server_put,
@@ -524,8 +1206,7 @@ mod test {
         hyper::Request::builder()
             .method(Method::PUT)
             .body(Body::from(""))
-            .unwrap(),
-        "127.0.0.1:8002"
+            .unwrap()
     );
     test_server_method!(
         test_server_delete,
@@ -533,8 +1214,7 @@ mod test {
         hyper::Request::builder()
             .method(Method::DELETE)
             .body(Body::from(""))
-            .unwrap(),
-        "127.0.0.1:8003"
+            .unwrap()
     );
     test_server_method!(
         test_server_patch,
@@ -542,8 +1222,7 @@ mod test {
         hyper::Request::builder()
             .method(Method::PATCH)
             .body(Body::from(""))
-            .unwrap(),
-        "127.0.0.1:8004"
+            .unwrap()
     );
 
     #[tokio::test]
@@ -554,11 +1233,8 @@ mod test {
             &(),
             &String::with_capacity(0),
         );
-        let response = run_req(
+        run_req(
             server,
-            "127.0.0.1:8005"
-                .parse()
-                .unwrap(),
             TestReq {
                 req: hyper::Request::builder()
                     .method(Method::HEAD)
@@ -568,8 +1244,6 @@ mod test {
             },
         )
         .await;
-
-        assert!(response.is_ok(), "{:?}", response);
     }
     test_server_method!(
         test_server_options,
@@ -577,8 +1251,7 @@ mod test {
         hyper::Request::builder()
             .method(Method::OPTIONS)
             .body(Body::from(""))
-            .unwrap(),
-        "127.0.0.1:8006"
+            .unwrap()
     );
     test_server_method!(
         test_server_trace,
@@ -586,8 +1259,7 @@ mod test {
         hyper::Request::builder()
             .method(Method::TRACE)
             .body(Body::from(""))
-            .unwrap(),
-        "127.0.0.1:8007"
+            .unwrap()
     );
     test_server_method!(
         test_server_all,
@@ -595,8 +1267,7 @@ mod test {
         hyper::Request::builder()
             .method(Method::GET)
             .body(Body::from(""))
-            .unwrap(),
-        "127.0.0.1:8008"
+            .unwrap()
     );
     test_with_server!(
         test_pre_error,
@@ -610,7 +1281,6 @@ mod test {
                 &(),
                 &String::with_capacity(0)
             ),
-        "127.0.0.1:8009",
         hyper::Request::builder()
             .method(Method::GET)
             .body(Body::from(""))
@@ -627,23 +1297,24 @@ mod test {
             .pre(|_| async {
                 crate::result::Result::from(Err(Error::new("PreMiddleware error".into(), 422)))
             })
-            .pre(|req: crate::result::Result<Request<String>>| async {
-                crate::result::Result::from(req.into_inner().map_or_else(
-                    |_| {
-                        Ok(crate::request::Request::new(String::from(
-                            "PreMiddleware fixed error",
-                        )))
-                    },
-                    Ok,
-                ))
-            })
+            .pre(
+                |req: crate::result::Result<Request<crate::body::Body>>| async {
+                    crate::result::Result::from(req.into_inner().map_or_else(
+                        |_| {
+                            Ok(crate::request::Request::new(
+                                "PreMiddleware fixed error".into(),
+                            ))
+                        },
+                        Ok,
+                    ))
+                }
+            )
             .get(
                 "/",
                 || async { "Hello world".to_owned() },
                 &(),
                 &String::with_capacity(0)
             ),
-        "127.0.0.1:8010",
         hyper::Request::builder()
             .method(Method::GET)
             .body(Body::from(""))
@@ -666,7 +1337,6 @@ mod test {
                 &(),
                 &String::with_capacity(0)
             ),
-        "127.0.0.1:8011",
         hyper::Request::builder()
             .method(Method::GET)
             .body(Body::from(""))
@@ -683,23 +1353,24 @@ mod test {
             .after(|_| async {
                 crate::result::Result::from(Err(Error::new("AfterMiddleware error".into(), 422)))
             })
-            .after(|res: crate::result::Result<Response<String>>| async {
-                crate::result::Result::from(res.into_inner().map_or_else(
-                    |_| {
-                        Ok(crate::response::Response::new(
-                            "AfterMiddleware Handled Error".to_owned(),
-                        ))
-                    },
-                    Ok,
-                ))
-            })
+            .after(
+                |res: crate::result::Result<Response<crate::body::Body>>| async {
+                    crate::result::Result::from(res.into_inner().map_or_else(
+                        |_| {
+                            Ok(crate::response::Response::new(
+                                "AfterMiddleware Handled Error".into(),
+                            ))
+                        },
+                        Ok,
+                    ))
+                }
+            )
             .get(
                 "/",
                 || async { "Hello world".to_owned() },
                 &(),
                 &String::with_capacity(0)
             ),
-        "127.0.0.1:8012",
         hyper::Request::builder()
             .method(Method::GET)
             .body(Body::from(""))
@@ -709,4 +1380,2172 @@ mod test {
             .body("AfterMiddleware Handled Error")
             .unwrap()
     );
+
+    test_with_server!(
+        test_path_params,
+        Server::new().get(
+            "/users/{user_id}/posts/{post_id}",
+            |crate::handler::Path((user_id, post_id)): crate::handler::Path<(u32, u32)>| async move {
+                format!("{} - {}", user_id, post_id)
+            },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/users/1/posts/2")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("1 - 2")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_invalid_path_params,
+        Server::new().get(
+            "/users/{id}",
+            |crate::handler::Path(id): crate::handler::Path<u32>| async move { id.to_string() },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/users/abc")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(400)
+            .body("Invalid path parameters: `abc`: invalid digit found in string")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_percent_encoded_path_params,
+        Server::new().get(
+            "/users/{name}",
+            |crate::handler::Path(name): crate::handler::Path<String>| async move { name },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/users/john%20doe")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("john doe")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_invalid_utf8_path_params,
+        Server::new().get(
+            "/users/{name}",
+            |crate::handler::Path(name): crate::handler::Path<String>| async move { name },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/users/john%FFdoe")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(400)
+            .body("Invalid path parameters: name is not valid UTF-8")
+            .unwrap()
+    );
+
+    async fn user_agent(
+        crate::handler::Path(id): crate::handler::Path<u64>,
+        headers: crate::request::HeaderMap,
+    ) -> String {
+        format!(
+            "{} - {}",
+            id,
+            headers
+                .get("user-agent")
+                .and_then(|agent| agent.to_str().ok())
+                .unwrap_or_default()
+        )
+    }
+
+    test_with_server!(
+        test_request_parts_as_last_argument,
+        Server::new().get("/users/{id}", user_agent, &(), &String::with_capacity(0)),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/users/7")
+            .header("user-agent", "yahf")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("7 - yahf")
+            .unwrap()
+    );
+
+    #[derive(serde::Deserialize)]
+    struct Pagination {
+        page: u32,
+        sort: Option<String>,
+    }
+
+    test_with_server!(
+        test_query,
+        Server::new().get(
+            "/",
+            |crate::handler::Query(pagination): crate::handler::Query<Pagination>| async move {
+                format!(
+                    "{} - {}",
+                    pagination.page,
+                    pagination
+                        .sort
+                        .unwrap_or_default()
+                )
+            },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/?page=2&sort=desc")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("2 - desc")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_invalid_query,
+        Server::new().get(
+            "/",
+            |query: crate::result::Result<crate::handler::Query<Pagination>>| async move {
+                query
+                    .into_inner()
+                    .map_or_else(|err| err.code().to_string(), |query| query.page.to_string())
+            },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/?page=first")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("400")
+            .unwrap()
+    );
+
+    #[derive(Clone)]
+    struct AppName(&'static str);
+
+    #[derive(Clone)]
+    struct RouterName(&'static str);
+
+    async fn state_middleware(
+        req: crate::result::Result<Request<crate::body::Body>>,
+    ) -> crate::result::Result<Request<crate::body::Body>> {
+        req.into_inner()
+            .and_then(
+                |req| match crate::handler::State::<AppName>::from_request(&req) {
+                    Some(_) => Ok(req),
+                    None => Err(Error::new("Missing state".into(), 500)),
+                },
+            )
+            .into()
+    }
+
+    test_with_server!(
+        test_server_state,
+        Server::new()
+            .with_state(AppName("yahf"))
+            .pre(state_middleware)
+            .get(
+                "/",
+                |crate::handler::State(name): crate::handler::State<AppName>| async move {
+                    name.0.to_string()
+                },
+                &(),
+                &String::with_capacity(0)
+            ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("yahf")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_router_state_through_merge,
+        Server::new()
+            .with_state(AppName("yahf"))
+            .router(
+                crate::router::Router::new()
+                    .router(
+                        crate::router::Router::new()
+                            .get(
+                                "/",
+                                |crate::handler::State(app): crate::handler::State<AppName>,
+                                 crate::handler::State(router): crate::handler::State<
+                                    RouterName,
+                                >| async move {
+                                    format!("{} - {}", app.0, router.0)
+                                },
+                                &(),
+                                &String::with_capacity(0)
+                            )
+                            .with_state(RouterName("inner"))
+                    )
+                    .with_state(RouterName("outer"))
+            ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("yahf - inner")
+            .unwrap()
+    );
+
+    #[test]
+    #[should_panic(
+        expected = "State of type yahf::server::test::RouterName required by GET /users/{id} was never attached"
+    )]
+    fn test_missing_state() {
+        TestClient::new(
+            Server::new()
+                .with_state(AppName("yahf"))
+                .nest(
+                    "/users",
+                    crate::router::Router::new().get(
+                        "/{id}",
+                        |crate::handler::State(router): crate::handler::State<RouterName>| async move {
+                            router.0.to_string()
+                        },
+                        &(),
+                        &String::with_capacity(0),
+                    ),
+                ),
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "State of type yahf::server::test::RouterName required by fallback /users was never attached"
+    )]
+    fn test_missing_state_on_fallback() {
+        TestClient::new(Server::new().nest(
+            "/users",
+            crate::router::Router::new().fallback(
+                |crate::handler::State(router): crate::handler::State<RouterName>| async move {
+                    router.0.to_string()
+                },
+                &(),
+                &String::with_capacity(0),
+            ),
+        ));
+    }
+
+    test_with_server!(
+        test_state_attached_after_the_route,
+        Server::new()
+            .route(
+                "/",
+                Route::new().get(
+                    |crate::handler::State(name): crate::handler::State<AppName>| async move {
+                        name.0.to_string()
+                    },
+                    &(),
+                    &String::with_capacity(0)
+                )
+            )
+            .with_state(AppName("yahf")),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("yahf")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_optional_state,
+        Server::new().get(
+            "/",
+            |state: crate::result::Result<crate::handler::State<AppName>>| async move {
+                state
+                    .into_inner()
+                    .map_or_else(|err| err.code().to_string(), |state| state.0 .0.to_string())
+            },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("500")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_binary_body,
+        Server::new().post(
+            "/",
+            |body: bytes::Bytes| async move { format!("{:?}", body.as_ref()) },
+            &bytes::Bytes::new(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .body(Body::from(vec![0xff, 0x00, 0x9f]))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("[255, 0, 159]")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_invalid_utf8_body,
+        Server::new().post(
+            "/",
+            |body: String| async move { body },
+            &String::with_capacity(0),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .body(Body::from(vec![0xff, 0x00, 0x9f]))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(400)
+            .body("Invalid UTF-8 body: invalid utf-8 sequence of 1 bytes from index 0")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_streaming_body,
+        Server::new().post(
+            "/",
+            |body: crate::body::Body| async move {
+                crate::body::Body::from_stream(futures::StreamExt::map(body, |chunk| {
+                    chunk.map(|chunk| chunk.to_ascii_uppercase())
+                }))
+            },
+            &(),
+            &()
+        ),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .body(Body::wrap_stream(futures::stream::iter(
+                ["Hello", " ", "world"].map(Ok::<_, std::io::Error>)
+            )))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("HELLO WORLD")
+            .unwrap()
+    );
+
+    async fn nested_middleware(
+        req: crate::result::Result<Request<crate::body::Body>>,
+    ) -> crate::result::Result<Request<crate::body::Body>> {
+        req.into_inner()
+            .map(|mut req| {
+                req.headers_mut()
+                    .insert("x-nested", "true".parse().unwrap());
+                req
+            })
+            .into()
+    }
+
+    async fn is_nested(req: Request<String>) -> String {
+        req.headers()
+            .contains_key("x-nested")
+            .to_string()
+    }
+
+    fn nested_server() -> Server<
+        impl PreMiddleware<
+            FutCallResponse = impl Future<
+                Output = impl Into<InternalResult<Request<crate::body::Body>>>,
+            >,
+        >,
+        impl AfterMiddleware<
+            FutCallResponse = impl Future<
+                Output = impl Into<InternalResult<Response<crate::body::Body>>>,
+            >,
+        >,
+    > {
+        Server::new()
+            .get(
+                "/orgs",
+                is_nested,
+                &String::with_capacity(0),
+                &String::with_capacity(0),
+            )
+            .nest(
+                "/orgs/{org}",
+                crate::router::Router::new()
+                    .pre(nested_middleware)
+                    .get(
+                        "/users/{id}",
+                        |crate::handler::Path((org, id)): crate::handler::Path<(String, u32)>,
+                         req: Request<String>| async move {
+                            format!("{} - {} - {}", org, id, is_nested(req).await)
+                        },
+                        &String::with_capacity(0),
+                        &String::with_capacity(0),
+                    ),
+            )
+    }
+
+    test_with_server!(
+        test_nested_router,
+        nested_server(),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/orgs/yahf/users/7")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("yahf - 7 - true")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_nested_middleware_is_scoped,
+        nested_server(),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/orgs")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("false")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_method_not_allowed,
+        Server::new()
+            .get(
+                "/users/{id}",
+                || async { "Hello world".to_owned() },
+                &(),
+                &String::with_capacity(0)
+            )
+            .delete(
+                "/users/{id}",
+                || async { "Hello world".to_owned() },
+                &(),
+                &String::with_capacity(0)
+            ),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .uri("/users/1")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(405)
+            .header("Allow", "GET, HEAD, DELETE, OPTIONS")
+            .body("")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_route_not_found,
+        Server::new().get(
+            "/users/{id}",
+            || async { "Hello world".to_owned() },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .uri("/posts/1")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(404)
+            .body("")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_head_falls_back_to_get,
+        Server::new().get(
+            "/",
+            || async { "Hello world".to_owned() },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::HEAD)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .header("Content-Length", "11")
+            .body("")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_explicit_head_overrides_get,
+        Server::new()
+            .get(
+                "/",
+                || async { "Hello world".to_owned() },
+                &(),
+                &String::with_capacity(0)
+            )
+            .head(
+                "/",
+                || async {
+                    crate::response::Response::builder()
+                        .status(204)
+                        .body(String::with_capacity(0))
+                        .map(crate::response::Response::from)
+                        .unwrap()
+                },
+                &(),
+                &String::with_capacity(0)
+            ),
+        hyper::Request::builder()
+            .method(Method::HEAD)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(204)
+            .body("")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_automatic_options,
+        Server::new()
+            .get(
+                "/users/{id}",
+                || async { "Hello world".to_owned() },
+                &(),
+                &String::with_capacity(0)
+            )
+            .patch(
+                "/users/{id}",
+                || async { "Hello world".to_owned() },
+                &(),
+                &String::with_capacity(0)
+            ),
+        hyper::Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/users/1")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(204)
+            .header("Allow", "GET, HEAD, OPTIONS, PATCH")
+            .body("")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_explicit_options_overrides_automatic,
+        Server::new()
+            .get(
+                "/",
+                || async { "Hello world".to_owned() },
+                &(),
+                &String::with_capacity(0)
+            )
+            .options(
+                "/",
+                || async { "Custom options".to_owned() },
+                &(),
+                &String::with_capacity(0)
+            ),
+        hyper::Request::builder()
+            .method(Method::OPTIONS)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("Custom options")
+            .unwrap()
+    );
+
+    async fn missed_header(
+        res: crate::result::Result<Response<crate::body::Body>>,
+    ) -> crate::result::Result<Response<crate::body::Body>> {
+        res.into_inner()
+            .map(|mut res| {
+                res.headers_mut()
+                    .insert("x-missed", "true".parse().unwrap());
+                res
+            })
+            .into()
+    }
+
+    async fn not_found(req: Request<String>) -> Response<String> {
+        Response::builder()
+            .status(404)
+            .body(format!("{} not found", req.uri().path()))
+            .unwrap()
+            .into()
+    }
+
+    test_with_server!(
+        test_fallback_runs_inside_middlewares,
+        Server::new()
+            .after(missed_header)
+            .get(
+                "/",
+                || async { "Hello world".to_owned() },
+                &(),
+                &String::with_capacity(0)
+            )
+            .fallback(
+                not_found,
+                &String::with_capacity(0),
+                &String::with_capacity(0)
+            ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/missing")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(404)
+            .header("x-missed", "true")
+            .body("/missing not found")
+            .unwrap()
+    );
+
+    fn fallback_server() -> Server<
+        impl PreMiddleware<
+            FutCallResponse = impl Future<
+                Output = impl Into<InternalResult<Request<crate::body::Body>>>,
+            >,
+        >,
+        impl AfterMiddleware<
+            FutCallResponse = impl Future<
+                Output = impl Into<InternalResult<Response<crate::body::Body>>>,
+            >,
+        >,
+    > {
+        Server::new()
+            .fallback(
+                not_found,
+                &String::with_capacity(0),
+                &String::with_capacity(0),
+            )
+            .nest(
+                "/orgs/{org}",
+                crate::router::Router::new()
+                    .get(
+                        "/users",
+                        || async { "Users".to_owned() },
+                        &(),
+                        &String::with_capacity(0),
+                    )
+                    .fallback(
+                        |crate::handler::Path(org): crate::handler::Path<String>| async move {
+                            format!("Nothing here for {}", org)
+                        },
+                        &(),
+                        &String::with_capacity(0),
+                    ),
+            )
+    }
+
+    test_with_server!(
+        test_nested_fallback,
+        fallback_server(),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/orgs/yahf/missing")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .body("Nothing here for yahf")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_fallback_outside_of_nested_prefix,
+        fallback_server(),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/users")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(404)
+            .body("/users not found")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_fallback_keeps_method_not_allowed,
+        fallback_server(),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .uri("/orgs/yahf/users")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(405)
+            .header("Allow", "GET, HEAD, OPTIONS")
+            .body("")
+            .unwrap()
+    );
+
+    async fn user_not_found() -> crate::result::Result<String> {
+        Err(Error::new("User not found".into(), 404).with_error_code("user_not_found")).into()
+    }
+
+    test_with_server!(
+        test_error_rendered_by_accept,
+        Server::new().get("/", user_not_found, &(), &String::with_capacity(0)),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .header("Accept", "text/html, application/json;q=0.9")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(404)
+            .header("Content-Type", "application/json")
+            .body(r#"{"code":"user_not_found","message":"User not found"}"#)
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_error_rendered_by_server_config,
+        Server::new()
+            .error_renderer(crate::error::JsonRenderer)
+            .get("/", user_not_found, &(), &String::with_capacity(0)),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .header("Accept", "text/plain")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(404)
+            .header("Content-Type", "application/json")
+            .body(r#"{"code":"user_not_found","message":"User not found"}"#)
+            .unwrap()
+    );
+
+    async fn created() -> (http::StatusCode, http::HeaderMap, String) {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(http::header::LOCATION, "/users/1".parse().unwrap());
+
+        (http::StatusCode::CREATED, headers, "Created".to_owned())
+    }
+
+    test_with_server!(
+        test_status_and_headers_from_tuple,
+        Server::new().post("/users", created, &(), &String::with_capacity(0)),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .uri("/users")
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(201)
+            .header("Location", "/users/1")
+            .body("Created")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_status_from_result_tuple,
+        Server::new().get(
+            "/",
+            || async {
+                crate::result::Result::from(Ok((http::StatusCode::ACCEPTED, "Accepted".to_owned())))
+            },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(202)
+            .body("Accepted")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_status_code_only,
+        Server::new().delete(
+            "/",
+            || async { http::StatusCode::NO_CONTENT },
+            &(),
+            &crate::handler::Json::<()>::new()
+        ),
+        hyper::Request::builder()
+            .method(Method::DELETE)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(204)
+            .body("")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_redirect,
+        Server::new().get(
+            "/",
+            || async { crate::response::Redirect::to("/home") },
+            &(),
+            &()
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(303)
+            .header("Location", "/home")
+            .body("")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_content_type_from_serializer,
+        Server::new().get(
+            "/",
+            || async { vec![1, 2, 3] },
+            &(),
+            &crate::handler::Json::new()
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .body("[1,2,3]")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_content_type_from_handler_is_kept,
+        Server::new().get(
+            "/",
+            || async {
+                let mut headers = http::HeaderMap::new();
+                headers.insert(http::header::CONTENT_TYPE, "text/csv".parse().unwrap());
+
+                (http::StatusCode::OK, headers, "a,b".to_owned())
+            },
+            &(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .header("Content-Type", "text/csv")
+            .body("a,b")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_unsupported_media_type,
+        Server::new().post(
+            "/",
+            |value: u32| async move { value.to_string() },
+            &crate::handler::Json::new(),
+            &String::with_capacity(0)
+        ),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .header("Content-Type", "text/plain")
+            .body(Body::from("1"))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(415)
+            .body("Unsupported Content-Type `text/plain`, expected one of: application/json, application/*+json")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_missing_body,
+        Server::new()
+            .config(
+                crate::server::ServerConfig::new()
+                    .request_timeout(std::time::Duration::from_secs(5))
+            )
+            .post(
+                "/",
+                |value: u32| async move { value.to_string() },
+                &crate::handler::Json::new(),
+                &String::with_capacity(0)
+            ),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .body(Body::from(""))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(400)
+            .body("Missing request body")
+            .unwrap()
+    );
+
+    #[derive(serde::Deserialize, serde::Serialize)]
+    struct Login {
+        username: String,
+    }
+
+    test_with_server!(
+        test_form_body,
+        Server::new().post(
+            "/login",
+            |login: Login| async move {
+                Login {
+                    username: login.username.to_uppercase(),
+                }
+            },
+            &crate::handler::Form::new(),
+            &crate::handler::Form::new()
+        ),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .uri("/login")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(Body::from("username=yahf"))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("username=YAHF")
+            .unwrap()
+    );
+
+    fn append_tag(
+        req: crate::result::Result<Request<crate::body::Body>>,
+        tag: &str,
+    ) -> crate::result::Result<Request<crate::body::Body>> {
+        req.into_inner()
+            .map(|mut req| {
+                let tags = req
+                    .headers()
+                    .get("x-tags")
+                    .map(|tags| {
+                        tags.to_str()
+                            .unwrap()
+                            .to_string()
+                    })
+                    .unwrap_or_default();
+                req.headers_mut().insert(
+                    "x-tags",
+                    format!("{}{};", tags, tag)
+                        .parse()
+                        .unwrap(),
+                );
+                req
+            })
+            .into()
+    }
+
+    async fn tag_request(
+        req: crate::result::Result<Request<crate::body::Body>>,
+    ) -> crate::result::Result<Request<crate::body::Body>> {
+        append_tag(req, "pre")
+    }
+
+    async fn route_tag_request(
+        req: crate::result::Result<Request<crate::body::Body>>,
+    ) -> crate::result::Result<Request<crate::body::Body>> {
+        append_tag(req, "route")
+    }
+
+    async fn route_tag_response(
+        res: crate::result::Result<crate::response::Response<crate::body::Body>>,
+    ) -> crate::result::Result<crate::response::Response<crate::body::Body>> {
+        res.into_inner()
+            .map(|mut res| {
+                res.headers_mut()
+                    .insert("x-route", "after".parse().unwrap());
+                res
+            })
+            .into()
+    }
+
+    async fn reject_blocked(
+        req: crate::result::Result<Request<crate::body::Body>>,
+    ) -> crate::result::Result<Request<crate::body::Body>> {
+        req.into_inner()
+            .and_then(|req| {
+                match req
+                    .headers()
+                    .contains_key("x-blocked")
+                {
+                    true => Err(Error::new("Blocked".into(), 403)),
+                    false => Ok(req),
+                }
+            })
+            .into()
+    }
+
+    #[tokio::test]
+    async fn test_server_middlewares_without_route() {
+        let client = crate::testing::TestClient::new(
+            Server::new()
+                .pre(reject_blocked)
+                .after(route_tag_response)
+                .get(
+                    "/users",
+                    tags,
+                    &String::with_capacity(0),
+                    &String::with_capacity(0),
+                ),
+        );
+
+        for (method, path, status) in [
+            (Method::GET, "/posts", 404),
+            (Method::POST, "/users", 405),
+            (Method::OPTIONS, "/users", 204),
+        ] {
+            client
+                .request(method.clone(), path)
+                .send()
+                .await
+                .assert_status(status)
+                .assert_header("x-route", "after");
+
+            client
+                .request(method, path)
+                .header("x-blocked", "true")
+                .send()
+                .await
+                .assert_status(403);
+        }
+    }
+
+    async fn tags(req: Request<String>) -> String {
+        req.headers()
+            .get("x-tags")
+            .map(|tags| {
+                tags.to_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_route_middlewares() {
+        let client = crate::testing::TestClient::new(
+            Server::new()
+                .pre(tag_request)
+                .get(
+                    "/",
+                    tags,
+                    &String::with_capacity(0),
+                    &String::with_capacity(0),
+                )
+                .route(
+                    "/admin",
+                    Route::new()
+                        .get(tags, &String::with_capacity(0), &String::with_capacity(0))
+                        .with_pre(route_tag_request)
+                        .with_after(route_tag_response),
+                ),
+        );
+
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_text("pre;")
+            .assert_no_header("x-route");
+
+        client
+            .get("/admin")
+            .send()
+            .await
+            .assert_text("pre;route;")
+            .assert_header("x-route", "after");
+    }
+
+    #[tokio::test]
+    async fn test_route_middlewares_on_every_method() {
+        let client = crate::testing::TestClient::new(
+            Server::new().route(
+                "/",
+                Route::new()
+                    .all(tags, &String::with_capacity(0), &String::with_capacity(0))
+                    .with_pre(route_tag_request)
+                    .with_pre(tag_request),
+            ),
+        );
+
+        client
+            .post("/")
+            .send()
+            .await
+            .assert_text("route;pre;");
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_text("route;pre;");
+    }
+
+    async fn elapsed(
+        req: crate::result::Result<Request<crate::body::Body>>,
+        next: crate::middleware::Next,
+    ) -> crate::result::Result<crate::response::Response<crate::body::Body>> {
+        let start = std::time::Instant::now();
+        next.run(req)
+            .await
+            .into_inner()
+            .map(|mut res| {
+                res.headers_mut()
+                    .insert("x-elapsed", (start.elapsed().as_millis() as u64).into());
+                res
+            })
+            .into()
+    }
+
+    #[tokio::test]
+    async fn test_around_middlewares() {
+        let client = crate::testing::TestClient::new(
+            Server::new()
+                .around(elapsed)
+                .get(
+                    "/",
+                    tags,
+                    &String::with_capacity(0),
+                    &String::with_capacity(0),
+                )
+                .route(
+                    "/admin",
+                    Route::new()
+                        .get(tags, &String::with_capacity(0), &String::with_capacity(0))
+                        .with_pre(route_tag_request)
+                        .with_around(route_around),
+                ),
+        );
+
+        let res = client.get("/").send().await;
+        res.assert_text("")
+            .assert_no_header("x-route");
+        assert!(res
+            .header("x-elapsed")
+            .is_some());
+
+        client
+            .get("/admin")
+            .send()
+            .await
+            .assert_text("route;")
+            .assert_header("x-route", "around");
+    }
+
+    async fn route_around(
+        req: crate::result::Result<Request<crate::body::Body>>,
+        next: crate::middleware::Next,
+    ) -> crate::result::Result<crate::response::Response<crate::body::Body>> {
+        next.run(req)
+            .await
+            .into_inner()
+            .map(|mut res| {
+                res.headers_mut()
+                    .insert("x-route", "around".parse().unwrap());
+                res
+            })
+            .into()
+    }
+
+    async fn tags_seen_by_around(
+        req: crate::result::Result<Request<crate::body::Body>>,
+        next: crate::middleware::Next,
+    ) -> crate::result::Result<crate::response::Response<crate::body::Body>> {
+        let req = req.into_inner();
+        let seen = req
+            .as_ref()
+            .ok()
+            .and_then(|req| {
+                req.headers()
+                    .get("x-tags")
+                    .cloned()
+            });
+
+        next.run(req.into())
+            .await
+            .into_inner()
+            .map(|mut res| {
+                if let Some(seen) = seen {
+                    res.headers_mut()
+                        .insert("x-around-saw", seen);
+                }
+                res
+            })
+            .into()
+    }
+
+    #[tokio::test]
+    async fn test_route_middlewares_order() {
+        // Appended in the wrong order on purpose: the PreMiddleware still runs before the
+        // AroundMiddlewares, and the AfterMiddleware after them
+        let client = crate::testing::TestClient::new(
+            Server::new().route(
+                "/",
+                Route::new()
+                    .get(tags, &String::with_capacity(0), &String::with_capacity(0))
+                    .with_after(route_tag_response)
+                    .with_around(route_around)
+                    .with_around(tags_seen_by_around)
+                    .with_pre(route_tag_request),
+            ),
+        );
+
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_text("route;")
+            .assert_header("x-around-saw", "route;")
+            .assert_header("x-route", "after");
+    }
+
+    type NegotiatedLogin =
+        crate::handler::Negotiate<(crate::handler::Json<Login>, crate::handler::Form<Login>)>;
+
+    async fn login() -> Login {
+        Login {
+            username: "yahf".into(),
+        }
+    }
+
+    test_with_server!(
+        test_negotiate_first_serializer_by_default,
+        Server::new().get("/login", login, &(), &NegotiatedLogin::new()),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/login")
+            .body(Body::empty())
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .header("Vary", "accept")
+            .body("{\"username\":\"yahf\"}")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_negotiate_by_quality,
+        Server::new().get("/login", login, &(), &NegotiatedLogin::new()),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/login")
+            .header(
+                "Accept",
+                "application/json;q=0.5, application/x-www-form-urlencoded"
+            )
+            .body(Body::empty())
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("Vary", "accept")
+            .body("username=yahf")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_negotiate_not_acceptable,
+        Server::new().get("/login", login, &(), &NegotiatedLogin::new()),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/login")
+            .header("Accept", "text/csv")
+            .body(Body::empty())
+            .unwrap(),
+        hyper::Response::builder()
+            .status(406)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .header("Vary", "accept")
+            .body("Not Acceptable, expected one of: application/json, application/x-www-form-urlencoded")
+            .unwrap()
+    );
+
+    #[cfg(any(
+        feature = "yaml",
+        feature = "toml",
+        feature = "cbor",
+        feature = "msgpack"
+    ))]
+    #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+    struct Profile {
+        name: String,
+        age: u8,
+    }
+
+    #[cfg(any(
+        feature = "yaml",
+        feature = "toml",
+        feature = "cbor",
+        feature = "msgpack"
+    ))]
+    async fn birthday(profile: Profile) -> Profile {
+        Profile {
+            age: profile.age + 1,
+            ..profile
+        }
+    }
+
+    /// Post a [Profile] in the format of the `$marker`, and negotiate the response between JSON
+    /// and that format
+    macro_rules! test_format {
+        ($name: ident, $feature: literal, $marker: ident, $media_type: literal, $encode: expr, $decode: expr) => {
+            #[cfg(feature = $feature)]
+            #[tokio::test]
+            async fn $name() {
+                let encode: fn(&Profile) -> Vec<u8> = $encode;
+                let decode: fn(&[u8]) -> Profile = $decode;
+                let client = TestClient::new(Server::new().post(
+                    "/birthday",
+                    birthday,
+                    &crate::handler::$marker::<Profile>::new(),
+                    &crate::handler::Negotiate::<(
+                        crate::handler::Json<Profile>,
+                        crate::handler::$marker<Profile>,
+                    )>::new(),
+                ));
+                let profile = Profile {
+                    name: "yahf".into(),
+                    age: 1,
+                };
+                let older = Profile {
+                    name: "yahf".into(),
+                    age: 2,
+                };
+
+                let response = client
+                    .post("/birthday")
+                    .header("Content-Type", $media_type)
+                    .header("Accept", $media_type)
+                    .body(encode(&profile))
+                    .send()
+                    .await;
+                response
+                    .assert_status(200)
+                    .assert_header("Content-Type", $media_type)
+                    .assert_header("Vary", "accept");
+                assert_eq!(decode(response.bytes()), older);
+
+                client
+                    .post("/birthday")
+                    .header("Content-Type", $media_type)
+                    .body(encode(&profile))
+                    .send()
+                    .await
+                    .assert_status(200)
+                    .assert_json(&older);
+
+                client
+                    .post("/birthday")
+                    .header("Content-Type", "application/json")
+                    .body(serde_json::to_vec(&profile).unwrap())
+                    .send()
+                    .await
+                    .assert_status(415);
+            }
+        };
+    }
+
+    test_format!(
+        test_yaml,
+        "yaml",
+        Yaml,
+        "application/yaml",
+        |profile| {
+            serde_norway::to_string(profile)
+                .unwrap()
+                .into_bytes()
+        },
+        |bytes| serde_norway::from_slice(bytes).unwrap()
+    );
+
+    test_format!(
+        test_toml,
+        "toml",
+        Toml,
+        "application/toml",
+        |profile| {
+            toml::to_string(profile)
+                .unwrap()
+                .into_bytes()
+        },
+        |bytes| toml::from_str(std::str::from_utf8(bytes).unwrap()).unwrap()
+    );
+
+    test_format!(
+        test_cbor,
+        "cbor",
+        Cbor,
+        "application/cbor",
+        |profile| {
+            let mut bytes = Vec::new();
+            ciborium::into_writer(profile, &mut bytes).unwrap();
+            bytes
+        },
+        |bytes| ciborium::from_reader(bytes).unwrap()
+    );
+
+    test_format!(
+        test_msgpack,
+        "msgpack",
+        MsgPack,
+        "application/msgpack",
+        |profile| rmp_serde::to_vec_named(profile).unwrap(),
+        |bytes| rmp_serde::from_slice(bytes).unwrap()
+    );
+
+    /// Serializer without a media type, that can't be negotiated
+    struct Untyped;
+
+    impl crate::serializer::BodySerializer for Untyped {
+        type Item = Login;
+
+        fn serialize(content: Self::Item) -> InternalResult<crate::body::Body> {
+            Ok(content.username.into())
+        }
+    }
+
+    test_with_server!(
+        test_negotiate_skips_serializers_without_media_type,
+        Server::new().get(
+            "/login",
+            login,
+            &(),
+            &crate::handler::Negotiate::<(Untyped, crate::handler::Json<Login>)>::new()
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/login")
+            .body(Body::empty())
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .header("Vary", "accept")
+            .body("{\"username\":\"yahf\"}")
+            .unwrap()
+    );
+
+    test_with_server!(
+        test_negotiate_not_acceptable_without_media_type,
+        Server::new().get(
+            "/login",
+            login,
+            &(),
+            &crate::handler::Negotiate::<(Untyped, crate::handler::Json<Login>)>::new()
+        ),
+        hyper::Request::builder()
+            .method(Method::GET)
+            .uri("/login")
+            .header("Accept", "*/*;q=0, text/plain")
+            .body(Body::empty())
+            .unwrap(),
+        hyper::Response::builder()
+            .status(406)
+            .header("Vary", "accept")
+            .body("Not Acceptable, expected one of: application/json")
+            .unwrap()
+    );
+
+    async fn upload(multipart: crate::multipart::Multipart) -> crate::result::Result<String> {
+        read_fields(multipart)
+            .await
+            .into()
+    }
+
+    async fn read_fields(
+        mut multipart: crate::multipart::Multipart,
+    ) -> Result<String, crate::error::Error> {
+        let mut fields = Vec::new();
+        while let Some(field) = multipart.next_field().await? {
+            let name = field
+                .name()
+                .unwrap_or_default()
+                .to_string();
+            fields.push(format!("{}={}", name, field.text().await?));
+        }
+
+        Ok(fields.join("&"))
+    }
+
+    const MULTIPART_BODY: &str = "--X\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        Holidays\r\n\
+        --X\r\n\
+        Content-Disposition: form-data; name=\"notes\"; filename=\"notes.txt\"\r\n\
+        Content-Type: text/plain\r\n\r\n\
+        Sun and sand\r\n\
+        --X--\r\n";
+
+    test_with_server!(
+        test_multipart_body,
+        Server::new().post("/upload", upload, &(), &String::with_capacity(0)),
+        hyper::Request::builder()
+            .method(Method::POST)
+            .uri("/upload")
+            .header("Content-Type", "multipart/form-data; boundary=X")
+            .body(Body::from(MULTIPART_BODY))
+            .unwrap(),
+        hyper::Response::builder()
+            .status(200)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body("title=Holidays&notes=Sun and sand")
+            .unwrap()
+    );
+
+    #[tokio::test]
+    async fn test_multipart_limits() {
+        let client = TestClient::new(
+            Server::new()
+                .pre(crate::multipart::MultipartLimits::new().total(16))
+                .post("/upload", upload, &(), &String::with_capacity(0))
+                .route(
+                    "/large",
+                    Route::new()
+                        .post(upload, &(), &String::with_capacity(0))
+                        .with_pre(crate::multipart::MultipartLimits::new().unlimited()),
+                ),
+        );
+
+        client
+            .post("/upload")
+            .header("Content-Type", "multipart/form-data; boundary=X")
+            .body(MULTIPART_BODY)
+            .send()
+            .await
+            .assert_status(413)
+            .assert_text("Multipart body too large: stream size exceeded limit: 16 bytes");
+
+        // The limits of the route replace the ones of the server
+        client
+            .post("/large")
+            .header("Content-Type", "multipart/form-data; boundary=X")
+            .body(MULTIPART_BODY)
+            .send()
+            .await
+            .assert_status(200)
+            .assert_text("title=Holidays&notes=Sun and sand");
+    }
+
+    #[derive(Default)]
+    struct RequestCounter(std::sync::atomic::AtomicUsize);
+
+    impl crate::middleware::Middleware for RequestCounter {
+        async fn pre(
+            &self,
+            req: crate::result::Result<Request<crate::body::Body>>,
+        ) -> crate::result::Result<Request<crate::body::Body>> {
+            let count = self
+                .0
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+                + 1;
+            append_tag(req, &count.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stateful_middleware_is_shared() {
+        let counter = crate::middleware::Stateful::new(RequestCounter::default());
+        let admin = crate::router::Router::new()
+            .pre(counter.clone())
+            .get(
+                "/admin",
+                tags,
+                &String::with_capacity(0),
+                &String::with_capacity(0),
+            );
+        let client = crate::testing::TestClient::new(
+            Server::new()
+                .pre(counter)
+                .get(
+                    "/",
+                    tags,
+                    &String::with_capacity(0),
+                    &String::with_capacity(0),
+                )
+                .router(admin),
+        );
+
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_text("1;");
+        client
+            .get("/admin")
+            .send()
+            .await
+            .assert_text("2;3;");
+    }
+
+    async fn echo(body: String) -> String {
+        body
+    }
+
+    fn limited_server() -> Server<
+        impl PreMiddleware<
+            FutCallResponse = impl Future<
+                Output = impl Into<InternalResult<Request<crate::body::Body>>>,
+            >,
+        >,
+        impl AfterMiddleware<
+            FutCallResponse = impl Future<
+                Output = impl Into<InternalResult<Response<crate::body::Body>>>,
+            >,
+        >,
+    > {
+        Server::new()
+            .limits(
+                crate::limits::Limits::new()
+                    .body(8)
+                    .header_count(4),
+            )
+            .post(
+                "/",
+                echo,
+                &String::with_capacity(0),
+                &String::with_capacity(0),
+            )
+            .post(
+                "/ignored",
+                || async { "ignored".to_string() },
+                &(),
+                &String::with_capacity(0),
+            )
+            .route(
+                "/upload",
+                Route::new()
+                    .post(echo, &String::with_capacity(0), &String::with_capacity(0))
+                    .body_limit(crate::limits::BodyLimit::new(32)),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_body_limit() {
+        let client = crate::testing::TestClient::new(limited_server());
+
+        client
+            .post("/")
+            .body("12345678")
+            .send()
+            .await
+            .assert_status(200)
+            .assert_text("12345678");
+        client
+            .post("/")
+            .body("123456789")
+            .send()
+            .await
+            .assert_status(413);
+        client
+            .post("/")
+            .header(http::header::CONTENT_LENGTH, "1024")
+            .body("1234")
+            .send()
+            .await
+            .assert_status(413);
+
+        // Checked before the handler, even when it never reads the body
+        client
+            .post("/ignored")
+            .header(http::header::CONTENT_LENGTH, "1024")
+            .body("1234")
+            .send()
+            .await
+            .assert_status(413);
+    }
+
+    #[tokio::test]
+    async fn test_route_body_limit() {
+        let client = crate::testing::TestClient::new(limited_server());
+
+        client
+            .post("/upload")
+            .body("0123456789abcdef")
+            .send()
+            .await
+            .assert_status(200)
+            .assert_text("0123456789abcdef");
+        client
+            .post("/upload")
+            .header(http::header::CONTENT_LENGTH, "64")
+            .body("0123456789abcdef")
+            .send()
+            .await
+            .assert_status(413);
+    }
+
+    #[tokio::test]
+    async fn test_header_limits() {
+        let client = crate::testing::TestClient::new(limited_server());
+
+        (1..=5)
+            .fold(client.post("/"), |req, index| {
+                req.header(format!("x-header-{}", index), "value")
+            })
+            .send()
+            .await
+            .assert_status(431);
+    }
+
+    #[tokio::test]
+    async fn test_header_size_while_parsing() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (_shutdown, signal) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(
+            limited_server()
+                .limits(crate::limits::Limits::new().header_size(1024))
+                .listen_on_with_shutdown(listener, async {
+                    let _ = signal.await;
+                }),
+        );
+
+        // hyper refuses the head before it's read whole, so it never reaches the error renderer
+        let mut stream = tokio::net::TcpStream::connect(addr)
+            .await
+            .unwrap();
+        let header = "a".repeat(64 * 1024);
+        let _ = stream
+            .write_all(
+                format!(
+                    "POST / HTTP/1.1\r\nHost: localhost\r\nx-large: {}\r\n\r\n",
+                    header
+                )
+                .as_bytes(),
+            )
+            .await;
+
+        // and the connection is closed right after the response
+        let mut response = String::new();
+        let _ = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            stream.read_to_string(&mut response),
+        )
+        .await
+        .unwrap();
+        assert!(response.starts_with("HTTP/1.1 431"));
+        assert!(!response.contains("Headers too large"));
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout() {
+        let client = TestClient::new(
+            Server::new()
+                .config(
+                    crate::server::ServerConfig::new()
+                        .read_timeout(std::time::Duration::from_millis(50)),
+                )
+                .post(
+                    "/",
+                    echo,
+                    &String::with_capacity(0),
+                    &String::with_capacity(0),
+                ),
+        );
+
+        let stalled = futures::StreamExt::chain(
+            futures::stream::iter([Ok::<_, std::io::Error>("Hello")]),
+            futures::stream::pending(),
+        );
+        client
+            .post("/")
+            .body(crate::body::Body::from_stream(stalled))
+            .send()
+            .await
+            .assert_status(408);
+    }
+
+    async fn listen_with_config(
+        config: crate::server::ServerConfig,
+    ) -> (SocketAddr, tokio::sync::oneshot::Sender<()>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (shutdown, signal) = tokio::sync::oneshot::channel::<()>();
+        let server = Server::new()
+            .get(
+                "/",
+                tags,
+                &String::with_capacity(0),
+                &String::with_capacity(0),
+            )
+            .config(config);
+        tokio::spawn(server.listen_on_with_shutdown(listener, async {
+            let _ = signal.await;
+        }));
+
+        (addr, shutdown)
+    }
+
+    async fn send_raw_request(stream: &mut tokio::net::TcpStream) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut buffer = [0; 1024];
+        let read = stream
+            .read(&mut buffer)
+            .await
+            .unwrap();
+        String::from_utf8_lossy(&buffer[..read]).into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_timeout() {
+        use tokio::io::AsyncReadExt;
+
+        let (addr, _shutdown) = listen_with_config(
+            crate::server::ServerConfig::new()
+                .keep_alive_timeout(std::time::Duration::from_millis(100)),
+        )
+        .await;
+
+        let mut stream = tokio::net::TcpStream::connect(addr)
+            .await
+            .unwrap();
+        assert!(send_raw_request(&mut stream)
+            .await
+            .starts_with("HTTP/1.1 200"));
+
+        // The idle connection gets closed, so reading ends
+        let closed =
+            tokio::time::timeout(std::time::Duration::from_secs(2), stream.read(&mut [0; 16]))
+                .await;
+        assert_eq!(closed.unwrap().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_timeout_spares_streamed_responses() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Every chunk takes longer than the idle timeout
+        let slow_stream = || async {
+            crate::body::Body::from_stream(futures::StreamExt::then(
+                futures::stream::iter(0..3),
+                |chunk| async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                    Ok::<_, std::io::Error>(format!("{};", chunk))
+                },
+            ))
+        };
+        let (_shutdown, signal) = tokio::sync::oneshot::channel::<()>();
+        let server = Server::new()
+            .get("/", slow_stream, &(), &())
+            .config(
+                crate::server::ServerConfig::new()
+                    .keep_alive_timeout(std::time::Duration::from_millis(50)),
+            );
+        tokio::spawn(server.listen_on_with_shutdown(listener, async {
+            let _ = signal.await;
+        }));
+
+        let response = Client::new()
+            .get(
+                format!("http://{}/", addr)
+                    .parse()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+
+        assert_eq!(body, "0;1;2;");
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_timeout_spares_http2() {
+        let (addr, _shutdown) = listen_with_config(
+            crate::server::ServerConfig::new()
+                .keep_alive_timeout(std::time::Duration::from_millis(100)),
+        )
+        .await;
+
+        let stream = tokio::net::TcpStream::connect(addr)
+            .await
+            .unwrap();
+        let (mut sender, connection) = hyper::client::conn::Builder::new()
+            .http2_only(true)
+            .handshake::<_, Body>(stream)
+            .await
+            .unwrap();
+        let mut connection = tokio::spawn(connection);
+
+        let request = || {
+            hyper::Request::builder()
+                .uri(format!("http://{}/", addr))
+                .body(Body::empty())
+                .unwrap()
+        };
+        assert_eq!(
+            sender
+                .send_request(request())
+                .await
+                .unwrap()
+                .status(),
+            200
+        );
+
+        // The idle HTTP/2 connection stays open, answering the pings of the server
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(300), &mut connection)
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            sender
+                .send_request(request())
+                .await
+                .unwrap()
+                .status(),
+            200
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_connections() {
+        let (addr, _shutdown) =
+            listen_with_config(crate::server::ServerConfig::new().max_connections(1)).await;
+
+        let mut first = tokio::net::TcpStream::connect(addr)
+            .await
+            .unwrap();
+        assert!(send_raw_request(&mut first)
+            .await
+            .starts_with("HTTP/1.1 200"));
+
+        let mut second = tokio::net::TcpStream::connect(addr)
+            .await
+            .unwrap();
+        let waiting = tokio::spawn(async move { send_raw_request(&mut second).await });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(!waiting.is_finished());
+
+        drop(first);
+        let response = tokio::time::timeout(std::time::Duration::from_secs(2), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+    }
+
+    #[tokio::test]
+    async fn test_http1_only() {
+        let (addr, _shutdown) =
+            listen_with_config(crate::server::ServerConfig::new().http1_only()).await;
+
+        let uri: hyper::Uri = format!("http://localhost:{}/", addr.port())
+            .parse()
+            .unwrap();
+        assert!(Client::builder()
+            .http2_only(true)
+            .build_http::<Body>()
+            .get(uri.clone())
+            .await
+            .is_err());
+        assert_eq!(
+            Client::new()
+                .get(uri)
+                .await
+                .unwrap()
+                .status(),
+            200
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeouts() {
+        let client = TestClient::new(
+            Server::new()
+                .around(crate::middleware::Timeout::new(
+                    std::time::Duration::from_millis(50),
+                ))
+                .get("/{millis}", slow, &(), &String::with_capacity(0))
+                .route(
+                    "/report/{millis}",
+                    Route::new()
+                        .get(slow, &(), &String::with_capacity(0))
+                        .with_around(crate::middleware::Timeout::new(
+                            std::time::Duration::from_millis(500),
+                        )),
+                )
+                .route(
+                    "/strict/{millis}",
+                    Route::new()
+                        .get(slow, &(), &String::with_capacity(0))
+                        .with_around(
+                            crate::middleware::Timeout::new(std::time::Duration::from_millis(10))
+                                .request_timeout(),
+                        ),
+                ),
+        );
+
+        client
+            .get("/10")
+            .send()
+            .await
+            .assert_status(200);
+        client
+            .get("/100")
+            .send()
+            .await
+            .assert_status(503);
+        client
+            .get("/report/100")
+            .send()
+            .await
+            .assert_status(200);
+        client
+            .get("/strict/30")
+            .send()
+            .await
+            .assert_status(408);
+    }
+
+    async fn slow(crate::handler::Path(millis): crate::handler::Path<u64>) -> String {
+        tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+        "Done".to_owned()
+    }
+
+    /// Listen on an ephemeral port and start a request to a handler that takes `millis` to
+    /// answer, returning once the handler is running
+    async fn start_slow_request(
+        shutdown_timeout: Option<std::time::Duration>,
+        millis: u64,
+    ) -> (
+        tokio::sync::oneshot::Sender<()>,
+        tokio::task::JoinHandle<Result<Shutdown, hyper::Error>>,
+        tokio::task::JoinHandle<Result<hyper::Response<Body>, hyper::Error>>,
+    ) {
+        let started = std::sync::Arc::new(tokio::sync::Notify::new());
+        let notify = started.clone();
+        let server = Server::new().get(
+            "/{millis}",
+            move |path: crate::handler::Path<u64>| {
+                notify.notify_one();
+                slow(path)
+            },
+            &(),
+            &String::with_capacity(0),
+        );
+        let server = match shutdown_timeout {
+            Some(timeout) => server.shutdown_timeout(timeout),
+            None => server,
+        };
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (shutdown, signal) = tokio::sync::oneshot::channel::<()>();
+        let listening = tokio::spawn(server.listen_on_with_shutdown(listener, async {
+            let _ = signal.await;
+        }));
+
+        let request = tokio::spawn(
+            Client::new().get(
+                format!("http://{}/{}", addr, millis)
+                    .parse()
+                    .unwrap(),
+            ),
+        );
+        started.notified().await;
+
+        (shutdown, listening, request)
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown_waits_for_requests() {
+        let (shutdown, listening, request) = start_slow_request(None, 200).await;
+        shutdown.send(()).unwrap();
+
+        let response = request
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            listening
+                .await
+                .unwrap()
+                .unwrap(),
+            Shutdown::Graceful
+        );
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown_timeout() {
+        let (shutdown, listening, request) =
+            start_slow_request(Some(std::time::Duration::from_millis(50)), 10_000).await;
+        shutdown.send(()).unwrap();
+
+        assert_eq!(
+            listening
+                .await
+                .unwrap()
+                .unwrap(),
+            Shutdown::TimedOut {
+                open_connections: 1
+            }
+        );
+        assert!(request
+            .await
+            .unwrap()
+            .is_err());
+    }
 }
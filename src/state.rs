@@ -0,0 +1,212 @@
+//! Storage for the states attached to a [Router](crate::router::Router) or a
+//! [Server](crate::server::Server)
+
+use std::{any::TypeId, sync::Arc};
+
+use http::Extensions;
+
+use crate::{
+    body::Body,
+    handler::{encapsulate_runner, BoxedHandler, State},
+    request::Request,
+    response::Response,
+    result::Result,
+    runner_input::RequiredState,
+};
+
+type StateInjector = Arc<dyn Fn(&mut Extensions) + Send + Sync>;
+
+/// States attached with `with_state`, in the order they were attached
+#[derive(Clone, Default)]
+pub struct States(Vec<(TypeId, StateInjector)>);
+
+impl States {
+    pub(crate) fn insert<S>(&mut self, state: S)
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        self.0.push((
+            TypeId::of::<S>(),
+            Arc::new(move |extensions: &mut Extensions| {
+                extensions.insert(State(state.clone()));
+            }),
+        ));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn contains(&self, state: &RequiredState) -> bool {
+        self.0
+            .iter()
+            .any(|(id, _)| *id == state.id())
+    }
+
+    /// Make every state available on the [Extensions] of a request
+    pub(crate) fn inject(&self, extensions: &mut Extensions) {
+        self.0
+            .iter()
+            .for_each(|(_, injector)| injector(extensions));
+    }
+
+    /// Wrap a handler so every request reaching it carries these states
+    pub(crate) fn wrap(&self, handler: BoxedHandler) -> BoxedHandler {
+        let states = self.clone();
+        let injected = move |req: Result<Request<Body>>| {
+            let handler = handler.clone();
+            let states = states.clone();
+            async move {
+                let req = req
+                    .into_inner()
+                    .map(|mut req| {
+                        states.inject(req.extensions_mut());
+                        req
+                    });
+
+                Result::<Response<Body>>::from(handler.call(req).await)
+            }
+        };
+
+        Box::new(encapsulate_runner(injected, &(), &()))
+    }
+}
+
+/// State required by the handler of a route
+#[derive(Clone)]
+struct Requirement {
+    /// [Method](crate::request::Method) of the route, or `fallback`
+    method: String,
+    path: String,
+    state: RequiredState,
+}
+
+/// States required by the routes of a [Router](crate::router::Router), that weren't attached to
+/// it yet
+///
+/// States can be attached after the routes that need them, so they are only checked once every
+/// state is known, when the [Server](crate::server::Server) starts
+#[derive(Clone, Default)]
+pub(crate) struct Requirements(Vec<Requirement>);
+
+impl Requirements {
+    pub(crate) fn require(&mut self, method: &str, path: &str, states: Vec<RequiredState>) {
+        self.0.extend(
+            states
+                .into_iter()
+                .map(|state| Requirement {
+                    method: method.to_string(),
+                    path: path.to_string(),
+                    state,
+                }),
+        );
+    }
+
+    /// Add the requirements of a [Router](crate::router::Router) mounted under the `prefix`,
+    /// except the ones met by its own `states`
+    pub(crate) fn nest(&mut self, prefix: &str, other: Requirements, states: &States) {
+        self.0.extend(
+            other
+                .0
+                .into_iter()
+                .filter(|requirement| !states.contains(&requirement.state))
+                .map(|requirement| Requirement {
+                    path: format!("{}{}", prefix, requirement.path),
+                    ..requirement
+                }),
+        );
+    }
+
+    /// Panic with every requirement the `states` don't meet
+    pub(crate) fn check(&self, states: &States) {
+        let missing: Vec<String> = self
+            .0
+            .iter()
+            .filter(|requirement| !states.contains(&requirement.state))
+            .map(|requirement| {
+                format!(
+                    "State of type {} required by {} {} was never attached",
+                    requirement.state.name(),
+                    requirement.method,
+                    if requirement.path.is_empty() {
+                        "/"
+                    } else {
+                        &requirement.path
+                    }
+                )
+            })
+            .collect();
+
+        if !missing.is_empty() {
+            panic!("{}", missing.join("\n"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Extensions;
+
+    use crate::{handler::State, runner_input::RequiredState};
+
+    use super::{Requirements, States};
+
+    #[test]
+    fn test_inject_states() {
+        let mut states = States::default();
+        states.insert(1u32);
+        states.insert(String::from("config"));
+
+        let mut extensions = Extensions::new();
+        states.inject(&mut extensions);
+
+        assert_eq!(extensions.get::<State<u32>>(), Some(&State(1)));
+        assert_eq!(
+            extensions.get::<State<String>>(),
+            Some(&State(String::from("config")))
+        );
+    }
+
+    #[test]
+    fn test_last_state_of_a_type_wins() {
+        let mut states = States::default();
+        states.insert(1u32);
+        states.insert(2u32);
+
+        let mut extensions = Extensions::new();
+        states.inject(&mut extensions);
+
+        assert_eq!(extensions.get::<State<u32>>(), Some(&State(2)));
+    }
+
+    #[test]
+    fn test_requirements_met_by_nested_states() {
+        let mut nested = Requirements::default();
+        nested.require(
+            "GET",
+            "/{id}",
+            vec![RequiredState::of::<u32>(), RequiredState::of::<String>()],
+        );
+
+        let mut states = States::default();
+        states.insert(1u32);
+
+        let mut requirements = Requirements::default();
+        requirements.nest("/users", nested, &states);
+
+        let mut server_states = States::default();
+        server_states.insert(String::from("config"));
+        requirements.check(&server_states);
+
+        let missing = std::panic::catch_unwind(|| requirements.check(&States::default()));
+        assert_eq!(
+            missing
+                .err()
+                .and_then(|err| err.downcast::<String>().ok())
+                .map(|err| *err),
+            Some(String::from(
+                "State of type alloc::string::String required by GET /users/{id} was never attached"
+            ))
+        );
+    }
+}
@@ -0,0 +1,401 @@
+//! Helpers to test a [Server] without opening a socket
+//!
+//! Refeer to the [TestClient] for more information
+
+use std::{fmt::Debug, sync::Arc};
+
+use bytes::Bytes;
+use futures::Future;
+use http::{HeaderMap, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    body::Body,
+    middleware::{AfterMiddleware, PreMiddleware},
+    request::{HttpBuilder, HttpRequest, Method, Request},
+    response::Response,
+    result::InternalResult,
+    server::{handle_req, Server},
+};
+
+/// Client that sends [Requests](crate::request::Request) straight to a [Server], through its
+/// routes and [`middlewares`](crate::middleware), without opening a socket
+///
+/// ```rust
+/// # use serde::{Deserialize, Serialize};
+/// use yahf::handler::Json;
+/// use yahf::server::Server;
+/// use yahf::testing::TestClient;
+///
+/// #[derive(Debug, PartialEq, Deserialize, Serialize)]
+/// struct Computation {
+///     value: u64,
+/// }
+///
+/// async fn increment(computation: Computation) -> Computation {
+///     Computation { value: computation.value + 1 }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let server = Server::new().post("/increment", increment, &Json::new(), &Json::new());
+/// let client = TestClient::new(server);
+///
+/// client
+///     .post("/increment")
+///     .json(&Computation { value: 1 })
+///     .send()
+///     .await
+///     .assert_status(200)
+///     .assert_json(&Computation { value: 2 });
+///
+/// client
+///     .get("/missing")
+///     .send()
+///     .await
+///     .assert_status(404);
+/// # }
+/// ```
+pub struct TestClient<PreM, AfterM> {
+    server: Arc<Server<PreM, AfterM>>,
+}
+
+macro_rules! request_method {
+    ($fn: ident, $method: expr, $method_ref: literal) => {
+        #[doc = std::concat!("Start building a ", $method_ref, " request to the `path`")]
+        pub fn $fn(&self, path: &str) -> TestRequest<'_, PreM, AfterM> {
+            self.request($method, path)
+        }
+    };
+}
+
+impl<PreM, FutP, ResultP, AfterM, FutA, ResultA> TestClient<PreM, AfterM>
+where
+    PreM: PreMiddleware<FutCallResponse = FutP> + 'static,
+    FutP: Future<Output = ResultP> + std::marker::Send + 'static,
+    ResultP: Into<InternalResult<Request<Body>>> + std::marker::Send + 'static,
+    AfterM: AfterMiddleware<FutCallResponse = FutA> + 'static,
+    FutA: Future<Output = ResultA> + std::marker::Send + 'static,
+    ResultA: Into<InternalResult<Response<Body>>> + std::marker::Send + 'static,
+{
+    /// Create a new [TestClient] for the [Server]
+    ///
+    /// Just like starting the [Server], it panics when a route requires a
+    /// [State](crate::handler::State) that was never attached
+    pub fn new(server: Server<PreM, AfterM>) -> Self {
+        server.check_states();
+
+        Self {
+            server: Arc::new(server),
+        }
+    }
+
+    /// Start building a request with the [Method] to the `path`
+    ///
+    /// The `path` can also carry a query, like `/users?page=1`
+    pub fn request(&self, method: Method, path: &str) -> TestRequest<'_, PreM, AfterM> {
+        TestRequest {
+            client: self,
+            builder: HttpBuilder::new().method(method),
+            path: path.to_string(),
+            body: Body::empty(),
+        }
+    }
+
+    request_method!(get, Method::GET, "[`GET`](Method::GET)");
+    request_method!(put, Method::PUT, "[`PUT`](Method::PUT)");
+    request_method!(delete, Method::DELETE, "[`DELETE`](Method::DELETE)");
+    request_method!(post, Method::POST, "[`POST`](Method::POST)");
+    request_method!(trace, Method::TRACE, "[`TRACE`](Method::TRACE)");
+    request_method!(options, Method::OPTIONS, "[`OPTIONS`](Method::OPTIONS)");
+    request_method!(connect, Method::CONNECT, "[`CONNECT`](Method::CONNECT)");
+    request_method!(patch, Method::PATCH, "[`PATCH`](Method::PATCH)");
+    request_method!(head, Method::HEAD, "[`HEAD`](Method::HEAD)");
+
+    /// Send an already built [Request](http::Request) to the [Server]
+    pub async fn send<B>(&self, req: HttpRequest<B>) -> TestResponse
+    where
+        B: Into<Body>,
+    {
+        let (parts, body) = handle_req(self.server.clone(), req)
+            .await
+            .expect("The server never fails to answer")
+            .into_parts();
+
+        let body = Body::from(body)
+            .bytes()
+            .await
+            .expect("Failed to read the response body");
+
+        TestResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        }
+    }
+}
+
+/// Builder of a request sent by a [TestClient]
+///
+/// Invalid values, like a malformed header, panic once the request is [`sent`](TestRequest::send)
+pub struct TestRequest<'a, PreM, AfterM> {
+    client: &'a TestClient<PreM, AfterM>,
+    builder: HttpBuilder,
+    path: String,
+    body: Body,
+}
+
+impl<'a, PreM, FutP, ResultP, AfterM, FutA, ResultA> TestRequest<'a, PreM, AfterM>
+where
+    PreM: PreMiddleware<FutCallResponse = FutP> + 'static,
+    FutP: Future<Output = ResultP> + std::marker::Send + 'static,
+    ResultP: Into<InternalResult<Request<Body>>> + std::marker::Send + 'static,
+    AfterM: AfterMiddleware<FutCallResponse = FutA> + 'static,
+    FutA: Future<Output = ResultA> + std::marker::Send + 'static,
+    ResultA: Into<InternalResult<Response<Body>>> + std::marker::Send + 'static,
+{
+    /// Append a header to the request
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        http::HeaderName: TryFrom<K>,
+        <http::HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        http::HeaderValue: TryFrom<V>,
+        <http::HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        self.builder = self
+            .builder
+            .header(key, value);
+        self
+    }
+
+    /// Append the `query`, encoded as `application/x-www-form-urlencoded`, to the path
+    pub fn query<T: Serialize>(mut self, query: &T) -> Self {
+        let query = serde_urlencoded::to_string(query).expect("Failed to encode the query");
+        let separator = if self.path.contains('?') { '&' } else { '?' };
+
+        self.path = format!("{}{}{}", self.path, separator, query);
+        self
+    }
+
+    /// Set the body of the request
+    pub fn body(mut self, body: impl Into<Body>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Set the body of the request to the `value` encoded as JSON, along with its
+    /// `Content-Type`
+    pub fn json<T: Serialize>(self, value: &T) -> Self {
+        let body = serde_json::to_vec(value).expect("Failed to encode the body");
+
+        self.header(http::header::CONTENT_TYPE, "application/json")
+            .body(body)
+    }
+
+    /// Send the request to the [Server]
+    ///
+    /// Like a real client, a `Content-Length` is added for the bodies that aren't streamed
+    pub async fn send(mut self) -> TestResponse {
+        let length = self
+            .body
+            .as_bytes()
+            .map(|bytes| bytes.len())
+            .filter(|length| *length > 0);
+        let has_length = self
+            .builder
+            .headers_ref()
+            .is_some_and(|headers| headers.contains_key(http::header::CONTENT_LENGTH));
+        if let (Some(length), false) = (length, has_length) {
+            self = self.header(http::header::CONTENT_LENGTH, length);
+        }
+
+        let req = self
+            .builder
+            .uri(self.path)
+            .body(self.body)
+            .expect("Invalid test request");
+
+        self.client.send(req).await
+    }
+}
+
+/// Response received by a [TestClient], with the body already read
+#[derive(Debug)]
+pub struct TestResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl TestResponse {
+    /// [StatusCode] of the response
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Headers of the response
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Value of the header `name`, when it's present and valid UTF-8
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// Body of the response
+    pub fn bytes(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Body of the response as text
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Body of the response deserialized from JSON
+    #[track_caller]
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|err| panic!("Invalid JSON body `{}`: {}", self.text(), err))
+    }
+
+    /// Assert the [StatusCode] of the response
+    #[track_caller]
+    pub fn assert_status<S>(&self, status: S) -> &Self
+    where
+        StatusCode: PartialEq<S>,
+        S: Debug,
+    {
+        assert!(
+            self.status == status,
+            "Expected status {:?}, found {} with body `{}`",
+            status,
+            self.status,
+            self.text()
+        );
+        self
+    }
+
+    /// Assert the value of the header `name`
+    #[track_caller]
+    pub fn assert_header(&self, name: &str, value: &str) -> &Self {
+        assert_eq!(self.header(name), Some(value), "Header `{}`", name);
+        self
+    }
+
+    /// Assert the header `name` isn't present
+    #[track_caller]
+    pub fn assert_no_header(&self, name: &str) -> &Self {
+        assert_eq!(self.header(name), None, "Header `{}`", name);
+        self
+    }
+
+    /// Assert the body of the response as text
+    #[track_caller]
+    pub fn assert_text(&self, text: &str) -> &Self {
+        assert_eq!(self.text(), text);
+        self
+    }
+
+    /// Assert the body of the response deserialized from JSON
+    #[track_caller]
+    pub fn assert_json<T>(&self, value: &T) -> &Self
+    where
+        T: DeserializeOwned + PartialEq + Debug,
+    {
+        assert_eq!(&self.json::<T>(), value);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        handler::{Json, Query},
+        request::Request,
+        server::Server,
+    };
+
+    use super::TestClient;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Page {
+        page: u32,
+    }
+
+    async fn echo_header(req: Request<String>) -> String {
+        req.headers()
+            .get("x-echo")
+            .map(|value| {
+                value
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_query_and_json_response() {
+        let client = TestClient::new(Server::new().get(
+            "/pages",
+            |Query(page): Query<Page>| async move { page },
+            &(),
+            &Json::new(),
+        ));
+
+        client
+            .get("/pages")
+            .query(&Page { page: 3 })
+            .send()
+            .await
+            .assert_status(200)
+            .assert_json(&Page { page: 3 });
+    }
+
+    #[tokio::test]
+    async fn test_json_request() {
+        let client = TestClient::new(Server::new().post(
+            "/pages",
+            |page: Page| async move { page.page.to_string() },
+            &Json::new(),
+            &String::with_capacity(0),
+        ));
+
+        client
+            .post("/pages")
+            .json(&Page { page: 7 })
+            .send()
+            .await
+            .assert_text("7");
+    }
+
+    #[tokio::test]
+    async fn test_headers_and_not_found() {
+        let client = TestClient::new(Server::new().get(
+            "/",
+            echo_header,
+            &String::with_capacity(0),
+            &String::with_capacity(0),
+        ));
+
+        client
+            .get("/")
+            .header("x-echo", "hello")
+            .send()
+            .await
+            .assert_text("hello");
+
+        client
+            .get("/missing")
+            .send()
+            .await
+            .assert_status(http::StatusCode::NOT_FOUND)
+            .assert_text("");
+    }
+}
@@ -3,8 +3,11 @@ use std::{collections::HashMap, sync::Arc};
 use futures::Future;
 
 use crate::{
+    body::Body,
     handler::{encapsulate_runner, BoxedHandler, RefHandler},
+    limits::BodyLimit,
     middleware::{AfterMiddleware, MiddlewareFactory, PreMiddleware},
+    path::PathParams,
     request::Request,
     response::Response,
     result::InternalResult,
@@ -15,6 +18,8 @@ struct Node<'a> {
     childrens: Option<HashMap<&'a str, Node<'a>>>,
     wildcard_node: Option<Box<Node<'a>>>,
     value: Option<BoxedHandler>,
+    body_limit: Option<BodyLimit>,
+    param_names: Vec<&'a str>,
 }
 
 #[derive(Default)]
@@ -26,6 +31,10 @@ fn is_parameter_declaration(value: &str) -> bool {
     value.starts_with('{') && value.ends_with('}')
 }
 
+fn parameter_name(value: &str) -> &str {
+    &value[1..value.len() - 1]
+}
+
 impl<'a> RouterTree<'a> {
     pub fn new() -> Self {
         Self {
@@ -42,8 +51,8 @@ impl<'a> RouterTree<'a> {
         AfterM: AfterMiddleware<FutCallResponse = FutA> + 'static,
         FutP: Future<Output = ResultP> + Send + 'static,
         FutA: Future<Output = ResultA> + Send + 'static,
-        ResultP: Into<InternalResult<Request<String>>> + Send + 'static,
-        ResultA: Into<InternalResult<Response<String>>> + Send + 'static,
+        ResultP: Into<InternalResult<Request<Body>>> + Send + 'static,
+        ResultA: Into<InternalResult<Response<Body>>> + Send + 'static,
     {
         Self::rec_apply(&mut self.root, middleware_factory);
         self
@@ -57,8 +66,8 @@ impl<'a> RouterTree<'a> {
         AfterM: AfterMiddleware<FutCallResponse = FutA> + 'static,
         FutP: Future<Output = ResultP> + Send + 'static,
         FutA: Future<Output = ResultA> + Send + 'static,
-        ResultP: Into<InternalResult<Request<String>>> + Send + 'static,
-        ResultA: Into<InternalResult<Response<String>>> + Send + 'static,
+        ResultP: Into<InternalResult<Request<Body>>> + Send + 'static,
+        ResultA: Into<InternalResult<Response<Body>>> + Send + 'static,
     {
         actual_node.apply_middlewares(middleware_factory.clone());
 
@@ -91,50 +100,99 @@ impl<'a> RouterTree<'a> {
         };
     }
 
+    pub fn map(mut self, f: &impl Fn(BoxedHandler) -> BoxedHandler) -> Self {
+        Self::rec_map(&mut self.root, f);
+        self
+    }
+
+    fn rec_map(actual_node: &mut Node<'a>, f: &impl Fn(BoxedHandler) -> BoxedHandler) {
+        actual_node.value = actual_node
+            .value
+            .take()
+            .map(f);
+
+        if let Some(childrens) = actual_node.childrens.as_mut() {
+            childrens
+                .iter_mut()
+                .for_each(|(_, node)| Self::rec_map(node, f));
+        }
+
+        if let Some(wildcard) = actual_node
+            .wildcard_node
+            .as_mut()
+        {
+            Self::rec_map(wildcard, f);
+        }
+    }
+
     pub fn extend(&mut self, another_handler: RouterTree<'a>) {
+        self.nest("", another_handler);
+    }
+
+    pub fn nest(&mut self, prefix: &str, another_handler: RouterTree<'a>) {
         let root = another_handler.root;
 
-        self.rec_extend(root, "".to_owned());
+        self.rec_extend(root, prefix.trim_end_matches('/'), Vec::new());
     }
 
-    fn rec_extend(&mut self, node: Node<'a>, mut path: String) {
-        if node.value.is_some() {
-            return self.insert(Box::leak(path.into_boxed_str()), node.value.unwrap());
+    fn rec_extend(&mut self, node: Node<'a>, prefix: &str, segments: Vec<Option<&'a str>>) {
+        if let Some(value) = node.value {
+            let mut param_names = node.param_names.into_iter();
+            let path = segments
+                .iter()
+                .map(|segment| match segment {
+                    Some(segment) => segment.to_string(),
+                    None => format!(
+                        "{{{}}}",
+                        param_names
+                            .next()
+                            .unwrap_or_default()
+                    ),
+                })
+                .fold(prefix.to_string(), |path, segment| {
+                    format!("{}/{}", path, segment)
+                });
+
+            self.insert_with_body_limit(Box::leak(path.into_boxed_str()), value, node.body_limit);
         }
 
-        match (node.childrens, node.wildcard_node) {
-            (None, None) => {}
-            (None, Some(wildcard_node)) => {
-                path.push_str("/{wildcard_node}");
-                self.rec_extend(*wildcard_node, path);
-            }
-            (Some(childrens), None) => {
-                childrens
-                    .into_iter()
-                    .for_each(|(next_path_segment, node)| {
-                        self.rec_extend(node, format!("{}/{}", path, next_path_segment));
-                    });
-            }
-            (Some(childrens), Some(wildcard_node)) => {
-                childrens
-                    .into_iter()
-                    .for_each(|(next_path_segment, node)| {
-                        self.rec_extend(node, format!("{}/{}", path, next_path_segment));
-                    });
+        if let Some(childrens) = node.childrens {
+            childrens
+                .into_iter()
+                .for_each(|(next_path_segment, node)| {
+                    let mut segments = segments.clone();
+                    segments.push(Some(next_path_segment));
+                    self.rec_extend(node, prefix, segments);
+                });
+        }
 
-                path.push_str("{wildcard_node}");
-                self.rec_extend(*wildcard_node, path);
-            }
-        };
+        if let Some(wildcard_node) = node.wildcard_node {
+            let mut segments = segments;
+            segments.push(None);
+            self.rec_extend(*wildcard_node, prefix, segments);
+        }
     }
 
     pub fn insert(&mut self, path: &'a str, handler: BoxedHandler) {
+        self.insert_with_body_limit(path, handler, None);
+    }
+
+    /// Bind the `handler` to the `path`, replacing the body limit of the
+    /// [Server](crate::server::Server) with the `body_limit` when there's one
+    pub fn insert_with_body_limit(
+        &mut self,
+        path: &'a str,
+        handler: BoxedHandler,
+        body_limit: Option<BodyLimit>,
+    ) {
         let mut node = &mut self.root;
+        let mut param_names = Vec::new();
         for splitted_path in path
             .split('/')
             .filter(|x| !x.is_empty())
         {
             if is_parameter_declaration(splitted_path) {
+                param_names.push(parameter_name(splitted_path));
                 node = node.add_wildcard_node();
                 continue;
             }
@@ -146,10 +204,37 @@ impl<'a> RouterTree<'a> {
             panic!("{} already defined", path);
         }
         node.value = Some(handler);
+        node.body_limit = body_limit;
+        node.param_names = param_names;
     }
 
-    pub fn get(&self, path: &str) -> Option<RefHandler<'_>> {
+    pub fn get(&self, path: &str) -> Option<(RefHandler<'_>, PathParams)> {
+        let (node, param_values) = self.find(path)?;
+
+        node.value
+            .as_ref()
+            .map(|boxed| {
+                let params = node
+                    .param_names
+                    .iter()
+                    .zip(param_values)
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect();
+
+                (boxed.as_ref(), PathParams::new(params))
+            })
+    }
+
+    /// Body limit of the route bound to the `path`, when it replaces the one of the
+    /// [Server](crate::server::Server)
+    pub fn body_limit(&self, path: &str) -> Option<BodyLimit> {
+        self.find(path)
+            .and_then(|(node, _)| node.body_limit)
+    }
+
+    fn find<'b>(&self, path: &'b str) -> Option<(&Node<'a>, Vec<&'b str>)> {
         let mut root = &self.root;
+        let mut param_values: Vec<&'b str> = Vec::new();
 
         for splitted_path in path
             .split('/')
@@ -157,7 +242,10 @@ impl<'a> RouterTree<'a> {
         {
             match (&root.childrens, &root.wildcard_node) {
                 (None, None) => return None,
-                (None, Some(wildcard_node)) => root = wildcard_node.as_ref(),
+                (None, Some(wildcard_node)) => {
+                    param_values.push(splitted_path);
+                    root = wildcard_node.as_ref();
+                }
                 (Some(childrens), None) => {
                     if childrens.contains_key(splitted_path) {
                         root = childrens
@@ -175,31 +263,20 @@ impl<'a> RouterTree<'a> {
                         continue;
                     }
 
+                    param_values.push(splitted_path);
                     root = wildcard_node.as_ref();
                 }
             }
         }
 
-        root.value
-            .as_ref()
-            .map(|boxed| boxed.as_ref())
+        Some((root, param_values))
     }
 }
 
 impl<'a> Node<'a> {
     fn add_wildcard_node(&mut self) -> &mut Self {
-        if self.wildcard_node.is_some() {
-            return self
-                .wildcard_node
-                .as_mut()
-                .unwrap()
-                .as_mut();
-        }
-
-        self.wildcard_node = Some(Box::default());
         self.wildcard_node
-            .as_mut()
-            .unwrap()
+            .get_or_insert_with(Box::default)
             .as_mut()
     }
 
@@ -211,21 +288,13 @@ impl<'a> Node<'a> {
         AfterM: AfterMiddleware<FutCallResponse = FutA> + 'static,
         FutP: Future<Output = ResultP> + Send + 'static,
         FutA: Future<Output = ResultA> + Send + 'static,
-        ResultP: Into<InternalResult<Request<String>>> + Send + 'static,
-        ResultA: Into<InternalResult<Response<String>>> + Send + 'static,
+        ResultP: Into<InternalResult<Request<Body>>> + Send + 'static,
+        ResultA: Into<InternalResult<Response<Body>>> + Send + 'static,
     {
         if let Some(value) = self.value.as_mut() {
-            let built = middleware_factory.build(
-                value.clone(),
-                &String::with_capacity(0),
-                &String::with_capacity(0),
-            );
+            let built = middleware_factory.build(value.clone(), &(), &());
 
-            self.value = Some(Box::new(encapsulate_runner(
-                built,
-                &String::with_capacity(0),
-                &String::with_capacity(0),
-            )));
+            self.value = Some(Box::new(encapsulate_runner(built, &(), &())));
         }
     }
 
@@ -247,7 +316,7 @@ impl<'a> Node<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::handler::encapsulate_runner;
+    use crate::{handler::encapsulate_runner, path::PathParams};
 
     use super::RouterTree;
 
@@ -398,4 +467,120 @@ mod tests {
             .get("/something")
             .is_some());
     }
+
+    #[test]
+    fn test_get_captures_path_params() {
+        let mut tree = RouterTree::new();
+
+        tree.insert(
+            "/users/{user_id}/posts/{post_id}",
+            Box::new(encapsulate_runner(
+                handler_example,
+                &(),
+                &String::with_capacity(0),
+            )),
+        );
+
+        tree.insert(
+            "/users/{id}",
+            Box::new(encapsulate_runner(
+                handler_example,
+                &(),
+                &String::with_capacity(0),
+            )),
+        );
+
+        let (_, params) = tree
+            .get("/users/10/posts/20")
+            .unwrap();
+        assert_eq!(
+            params,
+            PathParams::new(vec![
+                ("user_id".into(), "10".into()),
+                ("post_id".into(), "20".into())
+            ])
+        );
+
+        let (_, params) = tree.get("/users/10").unwrap();
+        assert_eq!(params, PathParams::new(vec![("id".into(), "10".into())]));
+    }
+
+    #[test]
+    fn test_extend_keeps_path_params_and_nested_routes() {
+        let mut tree = RouterTree::new();
+
+        let mut another_tree = RouterTree::new();
+
+        another_tree.insert(
+            "/users",
+            Box::new(encapsulate_runner(
+                handler_example,
+                &(),
+                &String::with_capacity(0),
+            )),
+        );
+
+        another_tree.insert(
+            "/users/{id}",
+            Box::new(encapsulate_runner(
+                handler_example,
+                &(),
+                &String::with_capacity(0),
+            )),
+        );
+
+        tree.extend(another_tree);
+
+        assert!(tree.get("/users").is_some());
+
+        let (_, params) = tree.get("/users/10").unwrap();
+        assert_eq!(params, PathParams::new(vec![("id".into(), "10".into())]));
+    }
+
+    #[test]
+    fn test_nest_under_parameterized_prefix() {
+        let mut tree = RouterTree::new();
+
+        let mut another_tree = RouterTree::new();
+
+        another_tree.insert(
+            "/",
+            Box::new(encapsulate_runner(
+                handler_example,
+                &(),
+                &String::with_capacity(0),
+            )),
+        );
+
+        another_tree.insert(
+            "/users/{id}",
+            Box::new(encapsulate_runner(
+                handler_example,
+                &(),
+                &String::with_capacity(0),
+            )),
+        );
+
+        tree.nest("/orgs/{org}/", another_tree);
+
+        assert!(tree
+            .get("/users/10")
+            .is_none());
+
+        let (_, params) = tree
+            .get("/orgs/yahf")
+            .unwrap();
+        assert_eq!(params, PathParams::new(vec![("org".into(), "yahf".into())]));
+
+        let (_, params) = tree
+            .get("/orgs/yahf/users/10")
+            .unwrap();
+        assert_eq!(
+            params,
+            PathParams::new(vec![
+                ("org".into(), "yahf".into()),
+                ("id".into(), "10".into())
+            ])
+        );
+    }
 }